@@ -1,50 +1,141 @@
+use std::borrow::Cow;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
 #[derive(Debug)]
+pub struct Node<K> {
+    pub kind: K,
+    pub span: Span,
+}
+
+#[derive(Clone, Copy, Debug)]
 pub enum BinaryOperator {
     Add,
     Subtract,
     Multiply,
     Divide,
+    Modulo,
     Equal,
     NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
     And,
     Or,
 }
 
+#[derive(Clone, Copy, Debug)]
+pub enum UnaryOperator {
+    Negate,
+    Not,
+}
+
 #[derive(Debug)]
 pub enum Literal<'a> {
     Bool(bool),
+    None,
     Number(f64),
-    Text(&'a str),
+    Text(Cow<'a, str>),
 }
 
+pub type Expression<'a> = Node<ExpressionKind<'a>>;
+
 #[derive(Debug)]
-pub enum Expression<'a> {
+pub enum ExpressionKind<'a> {
     Array(Vec<Expression<'a>>),
     BinaryOperator(Box<Expression<'a>>, BinaryOperator, Box<Expression<'a>>),
     Call(&'a str, Vec<Expression<'a>>),
     Index(Box<Expression<'a>>, Box<Expression<'a>>),
     Literal(Literal<'a>),
+    Map(Vec<(Expression<'a>, Expression<'a>)>),
     Member(Box<Expression<'a>>, &'a str),
     MethodCall(Box<Expression<'a>>, &'a str, Vec<Expression<'a>>),
+    UnaryOperator(UnaryOperator, Box<Expression<'a>>),
     Variable(&'a str),
 }
 
+pub type Statement<'a> = Node<StatementKind<'a>>;
+
 #[derive(Debug)]
-pub enum Statement<'a> {
-    Assign(&'a str, Expression<'a>),
+pub enum StatementKind<'a> {
+    Assign(Expression<'a>, Expression<'a>),
+    Break,
+    CompoundAssign(Expression<'a>, BinaryOperator, Expression<'a>),
+    Continue,
+    Enum(&'a str, Enum<'a>),
+    Expression(Expression<'a>),
+    For(&'a str, Iterable<'a>, Vec<Statement<'a>>),
     Function(&'a str, Function<'a>),
-    If(Expression<'a>, Vec<Statement<'a>>),
-    Return(Expression<'a>),
+    If(
+        Vec<(Expression<'a>, Vec<Statement<'a>>)>,
+        Option<Vec<Statement<'a>>>,
+    ),
+    Match(Expression<'a>, Vec<Node<Arm<'a>>>),
+    Return(Option<Expression<'a>>),
+    Struct(&'a str, Struct<'a>),
     While(Expression<'a>, Vec<Statement<'a>>),
 }
 
+#[derive(Debug)]
+pub enum Iterable<'a> {
+    Expression(Expression<'a>),
+    Range(Range<'a>),
+}
+
+#[derive(Debug)]
+pub struct Range<'a> {
+    pub start: Expression<'a>,
+    pub end: Expression<'a>,
+    pub inclusive: bool,
+    pub step: Option<Expression<'a>>,
+}
+
 #[derive(Debug)]
 pub struct Function<'a> {
     pub arguments: Vec<&'a str>,
     pub statements: Vec<Statement<'a>>,
 }
 
+#[derive(Debug)]
+pub struct Struct<'a> {
+    pub fields: Vec<&'a str>,
+    pub methods: Vec<(&'a str, Function<'a>)>,
+}
+
+#[derive(Debug)]
+pub struct Enum<'a> {
+    pub variants: Vec<(&'a str, Vec<&'a str>)>,
+}
+
+#[derive(Debug)]
+pub struct Arm<'a> {
+    pub pattern: Pattern<'a>,
+    pub guard: Option<Expression<'a>>,
+    pub statements: Vec<Statement<'a>>,
+}
+
+pub type Pattern<'a> = Node<PatternKind<'a>>;
+
+#[derive(Debug)]
+pub enum PatternKind<'a> {
+    Array(Vec<Pattern<'a>>),
+    Identifier(&'a str),
+    Literal(Literal<'a>),
+    Variant(&'a str, Vec<Pattern<'a>>),
+    Wildcard,
+}
+
+pub type Comment<'a> = Node<&'a str>;
+
 #[derive(Debug)]
 pub struct Program<'a> {
     pub statements: Vec<Statement<'a>>,
+    pub comments: Vec<Comment<'a>>,
 }
@@ -1,163 +1,860 @@
+mod error;
 pub mod value;
 
-use crate::ast::{BinaryOperator, Expression, Function, Literal, Program, Statement};
-use crate::interpreter::value::Value;
-use std::collections::HashMap;
+pub use crate::interpreter::error::RuntimeError;
+
+use crate::ast::{
+    Arm, BinaryOperator, Expression, ExpressionKind, Function, Iterable, Literal, Node, Pattern,
+    PatternKind, Program, Range, Statement, StatementKind, Struct, UnaryOperator,
+};
+use crate::interpreter::value::{Instance, Map, Value};
+use std::collections::{HashMap, HashSet};
 
 macro_rules! evalute_binary_expression {
     ($vop:ident $vlhs:ident $vrhs:ident $($op:ident $lhs:ident $rhs:ident => $t:ident $e:expr,)*) => {
         match ($vop, $vlhs, $vrhs) {
-            $((BinaryOperator::$op, Value::$lhs($vlhs), Value::$rhs($vrhs)) => Value::$t($e),)*
-            (op, lhs, rhs) => panic!("can't evaluate {lhs:?} {op:?} {rhs:?}"),
+            $((BinaryOperator::$op, Value::$lhs($vlhs), Value::$rhs($vrhs)) => Ok(Value::$t($e)),)*
+            (operator, lhs, rhs) => Err(RuntimeError::InvalidOperands { operator, lhs, rhs }),
         }
     };
 }
 
+const MAX_CALL_DEPTH: usize = 200;
+
+const MIN_CALL_STACK: usize = 256 * 1024;
+
+enum Signal {
+    Next,
+    Break,
+    Continue,
+    Return(Value),
+}
+
 struct State<'a> {
-    variables: HashMap<&'a str, Value>,
+    globals: HashMap<&'a str, Value>,
     functions: HashMap<&'a str, &'a Function<'a>>,
+    structs: HashMap<&'a str, &'a Struct<'a>>,
+    variants: HashMap<&'a str, &'a [&'a str]>,
+    enums: HashSet<&'a str>,
+    frames: Vec<HashMap<&'a str, Value>>,
 }
 
-impl State<'_> {
-    fn new() -> State<'static> {
+impl<'a> State<'a> {
+    fn new() -> State<'a> {
         State {
-            variables: HashMap::new(),
+            globals: HashMap::new(),
             functions: HashMap::new(),
+            structs: HashMap::new(),
+            variants: HashMap::new(),
+            enums: HashSet::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    fn variable(&self, name: &str) -> Option<&Value> {
+        let local = self.frames.last().and_then(|frame| frame.get(name));
+        local.or_else(|| self.globals.get(name))
+    }
+
+    fn variable_mut(&mut self, name: &'a str) -> Option<&mut Value> {
+        let Some(frame) = self.frames.last_mut() else {
+            return self.globals.get_mut(name);
+        };
+        if !frame.contains_key(name) {
+            frame.insert(name, self.globals.get(name)?.clone());
+        }
+        frame.get_mut(name)
+    }
+
+    fn assign(&mut self, name: &'a str, value: Value) {
+        let variables = self.frames.last_mut().unwrap_or(&mut self.globals);
+        variables.insert(name, value);
+    }
+
+    fn replace(&mut self, name: &'a str, value: Option<Value>) -> Option<Value> {
+        let variables = self.frames.last_mut().unwrap_or(&mut self.globals);
+        match value {
+            Some(value) => variables.insert(name, value),
+            None => variables.remove(name),
+        }
+    }
+
+    fn declare(&mut self, statement: &'a StatementKind<'a>) -> Result<(), RuntimeError> {
+        match statement {
+            StatementKind::Enum(name, enumeration) => {
+                self.check_declaration(name)?;
+                self.enums.insert(name);
+                for (variant, fields) in &enumeration.variants {
+                    self.check_declaration(variant)?;
+                    self.variants.insert(variant, fields);
+                }
+            }
+            StatementKind::Function(name, function) => {
+                self.check_declaration(name)?;
+                self.functions.insert(name, function);
+            }
+            StatementKind::Struct(name, structure) => {
+                self.check_declaration(name)?;
+                self.structs.insert(name, structure);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn fields(&self, name: &str) -> Option<&'a [&'a str]> {
+        match (self.variants.get(name), self.structs.get(name)) {
+            (Some(fields), _) => Some(fields),
+            (None, Some(structure)) => Some(&structure.fields),
+            (None, None) => None,
+        }
+    }
+
+    fn check_declaration(&self, name: &'a str) -> Result<(), RuntimeError> {
+        let function = self.functions.get_key_value(name).map(|(name, _)| name);
+        let structure = self.structs.get_key_value(name).map(|(name, _)| name);
+        let variant = self.variants.get_key_value(name).map(|(name, _)| name);
+        let enumeration = self.enums.get(name);
+        match function.or(structure).or(variant).or(enumeration) {
+            Some(declared) if !std::ptr::eq(*declared, name) => {
+                Err(RuntimeError::DuplicateDeclaration(name.to_owned()))
+            }
+            _ => Ok(()),
         }
     }
 }
 
-pub fn run<'a>(program: &'a Program<'a>) -> HashMap<&'a str, Value> {
+pub fn run<'a>(program: &'a Program<'a>) -> Result<HashMap<&'a str, Value>, RuntimeError> {
     let mut state = State::new();
-    run_statements(&program.statements, &mut state, false);
-    state.variables
+    for statement in &program.statements {
+        state.declare(&statement.kind)?;
+    }
+    run_statements(&program.statements, &mut state)?;
+    Ok(state.globals)
 }
 
 fn run_statements<'a>(
     statements: &'a [Statement<'a>],
     state: &mut State<'a>,
-    is_function: bool,
-) -> Option<Value> {
+) -> Result<Signal, RuntimeError> {
     for statement in statements {
-        match statement {
-            Statement::Assign(identifier, expression) => {
-                let value = evaluate(expression, state);
-                state.variables.insert(*identifier, value);
-            }
-            Statement::Function(identifier, function) => {
-                state.functions.insert(*identifier, function);
-            }
-            Statement::If(condition, statements) => {
-                let cond = match evaluate(condition, state) {
-                    Value::Bool(cond) => cond,
-                    cond => panic!("can't condition if with {cond:?}"),
-                };
-                if cond {
-                    if let Some(return_value) = run_statements(statements, state, is_function) {
-                        return Some(return_value);
-                    }
-                }
+        let signal = match &statement.kind {
+            StatementKind::Assign(target, expression) => {
+                let value = evaluate(expression, state)?;
+                assign(target, value, state)?;
+                Signal::Next
             }
-            Statement::Return(expression) => {
-                if is_function {
-                    return Some(evaluate(expression, state));
-                } else {
-                    panic!("can't return in top level function")
-                }
+            StatementKind::Break => Signal::Break,
+            StatementKind::CompoundAssign(target, op, expression) => {
+                compound_assign(target, *op, expression, state)?;
+                Signal::Next
             }
-            Statement::While(condition, statements) => loop {
-                let cond = match evaluate(condition, state) {
-                    Value::Bool(cond) => cond,
-                    cond => panic!("can't condition while with {cond:?}"),
-                };
-                if !cond {
-                    break;
-                }
-                if let Some(return_value) = run_statements(statements, state, is_function) {
-                    return Some(return_value);
+            StatementKind::Continue => Signal::Continue,
+            StatementKind::Enum(..) | StatementKind::Function(..) | StatementKind::Struct(..) => {
+                state.declare(&statement.kind)?;
+                Signal::Next
+            }
+            StatementKind::Expression(expression) => {
+                evaluate(expression, state)?;
+                Signal::Next
+            }
+            StatementKind::For(variable, iterable, statements) => {
+                run_for(variable, iterable, statements, state)?
+            }
+            StatementKind::If(branches, otherwise) => {
+                run_if(branches, otherwise.as_deref(), state)?
+            }
+            StatementKind::Match(subject, arms) => run_match(subject, arms, state)?,
+            StatementKind::Return(expression) => {
+                if state.frames.is_empty() {
+                    return Err(RuntimeError::ReturnOutsideFunction);
                 }
-            },
+                Signal::Return(match expression {
+                    Some(expression) => evaluate(expression, state)?,
+                    None => Value::None,
+                })
+            }
+            StatementKind::While(condition, statements) => run_while(condition, statements, state)?,
+        };
+        if !matches!(signal, Signal::Next) {
+            return Ok(signal);
+        }
+    }
+    Ok(Signal::Next)
+}
+
+fn run_if<'a>(
+    branches: &'a [(Expression<'a>, Vec<Statement<'a>>)],
+    otherwise: Option<&'a [Statement<'a>]>,
+    state: &mut State<'a>,
+) -> Result<Signal, RuntimeError> {
+    for (condition, statements) in branches {
+        if condition_holds(condition, state)? {
+            return run_statements(statements, state);
+        }
+    }
+    match otherwise {
+        Some(statements) => run_statements(statements, state),
+        None => Ok(Signal::Next),
+    }
+}
+
+fn run_match<'a>(
+    subject: &'a Expression<'a>,
+    arms: &'a [Node<Arm<'a>>],
+    state: &mut State<'a>,
+) -> Result<Signal, RuntimeError> {
+    let value = evaluate(subject, state)?;
+    for arm in arms {
+        check_pattern(&arm.kind.pattern, &mut Vec::new(), state)?;
+    }
+    for Node { kind: arm, .. } in arms {
+        let mut bindings = Vec::new();
+        if !pattern_matches(&arm.pattern, &value, &mut bindings, state) {
+            continue;
+        }
+        let guard_holds = match &arm.guard {
+            Some(guard) => guard_holds(guard, &bindings, state)?,
+            None => true,
+        };
+        if guard_holds {
+            for (name, value) in bindings {
+                state.assign(name, value);
+            }
+            return run_statements(&arm.statements, state);
         }
     }
-    None
+    Err(RuntimeError::NoMatch(value))
 }
 
-fn evaluate(expression: &Expression, state: &State) -> Value {
-    match expression {
-        Expression::Array(subexprs) => Value::Array(
-            subexprs
+fn guard_holds<'a>(
+    guard: &'a Expression<'a>,
+    bindings: &[(&'a str, Value)],
+    state: &mut State<'a>,
+) -> Result<bool, RuntimeError> {
+    let shadowed: Vec<_> = bindings
+        .iter()
+        .map(|(name, value)| (*name, state.replace(name, Some(value.clone()))))
+        .collect();
+    let holds = condition_holds(guard, state);
+    for (name, value) in shadowed.into_iter().rev() {
+        state.replace(name, value);
+    }
+    holds
+}
+
+fn check_pattern<'a>(
+    pattern: &'a Pattern<'a>,
+    bindings: &mut Vec<&'a str>,
+    state: &State<'a>,
+) -> Result<(), RuntimeError> {
+    match &pattern.kind {
+        PatternKind::Identifier(name) => match state.fields(name) {
+            Some(fields) => check_field_count(name, fields, 0),
+            None if bindings.contains(name) => {
+                Err(RuntimeError::DuplicateBinding((*name).to_owned()))
+            }
+            None => {
+                bindings.push(name);
+                Ok(())
+            }
+        },
+        PatternKind::Variant(name, patterns) => {
+            if let Some(fields) = state.fields(name) {
+                check_field_count(name, fields, patterns.len())?;
+            }
+            patterns
                 .iter()
-                .map(|subexpr| evaluate(subexpr, state))
-                .collect(),
-        ),
-        Expression::BinaryOperator(lhs, op, rhs) => {
-            let lhs = evaluate(lhs, state);
-            let rhs = evaluate(rhs, state);
-            evalute_binary_expression! {
-                op lhs rhs
-                Add Number Number => Number lhs + rhs,
-                Add Text Text => Text lhs + &rhs,
-                Subtract Number Number => Number lhs - rhs,
-                Multiply Number Number => Number lhs * rhs,
-                Divide Number Number => Number lhs / rhs,
-                Equal Number Number => Bool lhs == rhs,
-                Equal Text Text => Bool lhs == rhs,
-                NotEqual Number Number => Bool lhs != rhs,
-                NotEqual Text Text => Bool lhs != rhs,
-                And Bool Bool => Bool lhs && rhs,
-                Or Bool Bool => Bool lhs || rhs,
-            }
-        }
-        Expression::Call(function, arguments) => {
-            assert_eq!(arguments.len(), state.functions[function].arguments.len());
-            let mut call_state = State::new();
-            for (i, argument) in arguments.iter().enumerate() {
-                let arg_name = state.functions[function].arguments[i];
-                let arg_value = evaluate(argument, state);
-                call_state.variables.insert(arg_name, arg_value);
-            }
-            let return_value =
-                run_statements(&state.functions[function].statements, &mut call_state, true);
-            return_value.unwrap()
-        }
-        Expression::Index(array, index) => {
-            let array = evaluate(array, state);
-            let index = evaluate(index, state);
-            match (array, index) {
-                (Value::Array(array), Value::Number(index)) => array[index as usize].clone(),
-                (array, index) => panic!("can't index {array:?} with {index:?}"),
-            }
-        }
-        Expression::Literal(literal) => match literal {
-            Literal::Bool(bool) => Value::Bool(*bool),
-            Literal::Number(number) => Value::Number(*number),
-            Literal::Text(text) => Value::Text((*text).to_owned()),
+                .try_for_each(|pattern| check_pattern(pattern, bindings, state))
+        }
+        PatternKind::Array(patterns) => patterns
+            .iter()
+            .try_for_each(|pattern| check_pattern(pattern, bindings, state)),
+        PatternKind::Literal(_) | PatternKind::Wildcard => Ok(()),
+    }
+}
+
+fn pattern_matches<'a>(
+    pattern: &'a Pattern<'a>,
+    value: &Value,
+    bindings: &mut Vec<(&'a str, Value)>,
+    state: &State<'a>,
+) -> bool {
+    match (&pattern.kind, value) {
+        (PatternKind::Wildcard, _) => true,
+        (PatternKind::Literal(literal), value) => literal_value(literal) == *value,
+        (PatternKind::Identifier(name), value) => match state.fields(name) {
+            Some(_) => is_instance(value, name, 0),
+            _ => {
+                bindings.push((name, value.clone()));
+                true
+            }
         },
-        Expression::Member(object, member) => {
-            let object = evaluate(object, state);
-            match (object, *member) {
-                (Value::Text(text), "length") => Value::Number(text.len() as f64),
-                (Value::Array(elements), "length") => Value::Number(elements.len() as f64),
-                (object, _) => panic!("unknown member {member:?} of value {object:?}"),
-            }
-        }
-        Expression::MethodCall(object, method, arguments) => {
-            match (evaluate(object, state), *method, arguments.as_slice()) {
-                (Value::Text(haystack), "starts_with", [needle]) => {
-                    let needle = match evaluate(needle, state) {
-                        Value::Text(needle) => needle,
-                        needle => {
-                            panic!("can't call method starts_with of text with argument {needle:?}")
-                        }
-                    };
-                    Value::Bool(haystack.starts_with(&needle))
+        (PatternKind::Variant(name, patterns), Value::Struct(instance)) => {
+            is_instance(value, name, patterns.len())
+                && patterns
+                    .iter()
+                    .zip(&instance.fields)
+                    .all(|(pattern, (_, value))| pattern_matches(pattern, value, bindings, state))
+        }
+        (PatternKind::Array(patterns), Value::Array(elements)) => {
+            patterns.len() == elements.len()
+                && patterns
+                    .iter()
+                    .zip(elements)
+                    .all(|(pattern, value)| pattern_matches(pattern, value, bindings, state))
+        }
+        _ => false,
+    }
+}
+
+fn is_instance(value: &Value, name: &str, fields: usize) -> bool {
+    match value {
+        Value::Struct(instance) => instance.name == name && instance.fields.len() == fields,
+        _ => false,
+    }
+}
+
+fn run_while<'a>(
+    condition: &'a Expression<'a>,
+    statements: &'a [Statement<'a>],
+    state: &mut State<'a>,
+) -> Result<Signal, RuntimeError> {
+    while condition_holds(condition, state)? {
+        match run_statements(statements, state)? {
+            Signal::Break => break,
+            Signal::Return(value) => return Ok(Signal::Return(value)),
+            Signal::Next | Signal::Continue => {}
+        }
+    }
+    Ok(Signal::Next)
+}
+
+fn run_for<'a>(
+    variable: &'a str,
+    iterable: &'a Iterable<'a>,
+    statements: &'a [Statement<'a>],
+    state: &mut State<'a>,
+) -> Result<Signal, RuntimeError> {
+    let values: Box<dyn Iterator<Item = Value>> = match iterable {
+        Iterable::Expression(expression) => match evaluate(expression, state)? {
+            Value::Array(elements) => Box::new(elements.into_iter()),
+            Value::Text(text) => {
+                let characters: Vec<_> = text.chars().collect();
+                Box::new(characters.into_iter().map(|c| Value::Text(c.to_string())))
+            }
+            value => return Err(RuntimeError::InvalidIterable(value)),
+        },
+        Iterable::Range(range) => Box::new(range_values(range, state)?),
+    };
+    for value in values {
+        state.assign(variable, value);
+        match run_statements(statements, state)? {
+            Signal::Break => break,
+            Signal::Return(value) => return Ok(Signal::Return(value)),
+            Signal::Next | Signal::Continue => {}
+        }
+    }
+    Ok(Signal::Next)
+}
+
+fn range_values<'a>(
+    range: &'a Range<'a>,
+    state: &mut State<'a>,
+) -> Result<impl Iterator<Item = Value>, RuntimeError> {
+    let start = range_number(&range.start, state)?;
+    let end = range_number(&range.end, state)?;
+    let step = match &range.step {
+        Some(step) => range_number(step, state)?,
+        None => 1.,
+    };
+    if step == 0. {
+        return Err(RuntimeError::InvalidRange(Value::Number(step)));
+    }
+    let inclusive = range.inclusive;
+    let values = (0_u64..).map(move |i| start + i as f64 * step);
+    let values = values.take_while(move |&value| match (step > 0., inclusive) {
+        (true, false) => value < end,
+        (true, true) => value <= end,
+        (false, false) => value > end,
+        (false, true) => value >= end,
+    });
+    Ok(values.map(Value::Number))
+}
+
+fn range_number<'a>(
+    expression: &'a Expression<'a>,
+    state: &mut State<'a>,
+) -> Result<f64, RuntimeError> {
+    match evaluate(expression, state)? {
+        Value::Number(number) => Ok(number),
+        value => Err(RuntimeError::InvalidRange(value)),
+    }
+}
+
+fn assign<'a>(
+    target: &'a Expression<'a>,
+    value: Value,
+    state: &mut State<'a>,
+) -> Result<(), RuntimeError> {
+    let mut path = Vec::new();
+    let name = target_path(target, &mut path, state)?;
+    store(name, &path, value, state)
+}
+
+fn compound_assign<'a>(
+    target: &'a Expression<'a>,
+    op: BinaryOperator,
+    expression: &'a Expression<'a>,
+    state: &mut State<'a>,
+) -> Result<(), RuntimeError> {
+    let mut path = Vec::new();
+    let name = target_path(target, &mut path, state)?;
+    let lhs = place(name, &path, state)?.clone();
+    let rhs = evaluate(expression, state)?;
+    let value = evaluate_binary_operator(op, lhs, rhs)?;
+    store(name, &path, value, state)
+}
+
+fn store<'a>(
+    name: &'a str,
+    path: &[Step],
+    value: Value,
+    state: &mut State<'a>,
+) -> Result<(), RuntimeError> {
+    let Some((last, path)) = path.split_last() else {
+        state.assign(name, value);
+        return Ok(());
+    };
+    match (place(name, path, state)?, last) {
+        (Value::Map(map), Step::Index(key)) => {
+            check_key(key)?;
+            map.insert(key.clone(), value);
+        }
+        (object, step) => *step_place(object, step)? = value,
+    }
+    Ok(())
+}
+
+fn place<'s, 'a>(
+    name: &'a str,
+    path: &[Step],
+    state: &'s mut State<'a>,
+) -> Result<&'s mut Value, RuntimeError> {
+    let Some(mut place) = state.variable_mut(name) else {
+        return Err(RuntimeError::UndefinedVariable(name.to_owned()));
+    };
+    for step in path {
+        place = step_place(place, step)?;
+    }
+    Ok(place)
+}
+
+fn step_place<'v>(object: &'v mut Value, step: &Step) -> Result<&'v mut Value, RuntimeError> {
+    match step {
+        Step::Index(index) => index_place(object, index),
+        Step::Member(member) => member_place(object, member),
+    }
+}
+
+fn load(name: &str, path: &[Step], state: &State) -> Result<Value, RuntimeError> {
+    let Some(mut value) = state.variable(name).cloned() else {
+        return Err(RuntimeError::UndefinedVariable(name.to_owned()));
+    };
+    for step in path {
+        value = match step {
+            Step::Index(index) => index_value(value, index.clone())?,
+            Step::Member(member) => member_value(value, member)?,
+        };
+    }
+    Ok(value)
+}
+
+fn place_root<'a>(expression: &Expression<'a>) -> Option<&'a str> {
+    match &expression.kind {
+        ExpressionKind::Variable(name) => Some(name),
+        ExpressionKind::Index(object, _) | ExpressionKind::Member(object, _) => place_root(object),
+        _ => None,
+    }
+}
+
+enum Step<'a> {
+    Index(Value),
+    Member(&'a str),
+}
+
+fn target_path<'a>(
+    target: &'a Expression<'a>,
+    path: &mut Vec<Step<'a>>,
+    state: &mut State<'a>,
+) -> Result<&'a str, RuntimeError> {
+    match &target.kind {
+        ExpressionKind::Variable(name) => Ok(name),
+        ExpressionKind::Index(object, index) => {
+            let name = target_path(object, path, state)?;
+            path.push(Step::Index(evaluate(index, state)?));
+            Ok(name)
+        }
+        ExpressionKind::Member(object, member) => {
+            let name = target_path(object, path, state)?;
+            path.push(Step::Member(member));
+            Ok(name)
+        }
+        _ => Err(RuntimeError::InvalidAssignTarget),
+    }
+}
+
+fn condition_holds<'a>(
+    condition: &'a Expression<'a>,
+    state: &mut State<'a>,
+) -> Result<bool, RuntimeError> {
+    match evaluate(condition, state)? {
+        Value::Bool(cond) => Ok(cond),
+        cond => Err(RuntimeError::InvalidCondition(cond)),
+    }
+}
+
+fn evaluate<'a>(
+    expression: &'a Expression<'a>,
+    state: &mut State<'a>,
+) -> Result<Value, RuntimeError> {
+    match &expression.kind {
+        ExpressionKind::Array(subexprs) => Ok(Value::Array(evaluate_all(subexprs, state)?)),
+        ExpressionKind::BinaryOperator(lhs, op, rhs) => {
+            let lhs = evaluate(lhs, state)?;
+            match (op, lhs) {
+                (BinaryOperator::And, Value::Bool(false)) => Ok(Value::Bool(false)),
+                (BinaryOperator::Or, Value::Bool(true)) => Ok(Value::Bool(true)),
+                (op, lhs) => {
+                    let rhs = evaluate(rhs, state)?;
+                    evaluate_binary_operator(*op, lhs, rhs)
                 }
-                (object, method, args) => panic!(
-                    "unknown method {method:?} of value {object:?} with {} arguments",
-                    args.len()
-                ),
             }
         }
-        Expression::Variable(variable) => state.variables[*variable].clone(),
+        ExpressionKind::Call(function, arguments) => call(function, arguments, state),
+        ExpressionKind::Index(array, index) => {
+            let array = evaluate(array, state)?;
+            let index = evaluate(index, state)?;
+            index_value(array, index)
+        }
+        ExpressionKind::Literal(literal) => Ok(literal_value(literal)),
+        ExpressionKind::Map(entries) => evaluate_map(entries, state),
+        ExpressionKind::Member(object, member) => {
+            let object = evaluate(object, state)?;
+            member_value(object, member)
+        }
+        ExpressionKind::MethodCall(object, method, arguments) => {
+            method_call(object, method, arguments, state)
+        }
+        ExpressionKind::UnaryOperator(op, operand) => {
+            let operand = evaluate(operand, state)?;
+            evaluate_unary_operator(*op, operand)
+        }
+        ExpressionKind::Variable(variable) => variable_value(variable, state),
+    }
+}
+
+fn literal_value(literal: &Literal) -> Value {
+    match literal {
+        Literal::Bool(bool) => Value::Bool(*bool),
+        Literal::None => Value::None,
+        Literal::Number(number) => Value::Number(*number),
+        Literal::Text(text) => Value::Text(text.to_string()),
+    }
+}
+
+fn variable_value(name: &str, state: &State) -> Result<Value, RuntimeError> {
+    match (state.variable(name), state.variants.get(name)) {
+        (Some(value), _) => Ok(value.clone()),
+        (None, Some(fields)) if fields.is_empty() => construct(name, fields, Vec::new()),
+        (None, _) => Err(RuntimeError::UndefinedVariable(name.to_owned())),
+    }
+}
+
+fn evaluate_all<'a>(
+    expressions: &'a [Expression<'a>],
+    state: &mut State<'a>,
+) -> Result<Vec<Value>, RuntimeError> {
+    expressions
+        .iter()
+        .map(|expression| evaluate(expression, state))
+        .collect()
+}
+
+fn evaluate_map<'a>(
+    entries: &'a [(Expression<'a>, Expression<'a>)],
+    state: &mut State<'a>,
+) -> Result<Value, RuntimeError> {
+    let mut map = Map::default();
+    for (key, value) in entries {
+        let key = evaluate(key, state)?;
+        check_key(&key)?;
+        let value = evaluate(value, state)?;
+        map.insert(key, value);
+    }
+    Ok(Value::Map(map))
+}
+
+fn call<'a>(
+    name: &str,
+    arguments: &'a [Expression<'a>],
+    state: &mut State<'a>,
+) -> Result<Value, RuntimeError> {
+    if let Some(&function) = state.functions.get(name) {
+        let arguments = evaluate_all(arguments, state)?;
+        call_function(name, function, None, arguments, state)
+    } else if let Some(&structure) = state.structs.get(name) {
+        let arguments = evaluate_all(arguments, state)?;
+        construct(name, &structure.fields, arguments)
+    } else if let Some(&fields) = state.variants.get(name) {
+        let arguments = evaluate_all(arguments, state)?;
+        construct(name, fields, arguments)
+    } else {
+        Err(RuntimeError::UndefinedFunction(name.to_owned()))
+    }
+}
+
+fn call_function<'a>(
+    name: &str,
+    function: &'a Function<'a>,
+    mut receiver: Option<&mut Value>,
+    arguments: Vec<Value>,
+    state: &mut State<'a>,
+) -> Result<Value, RuntimeError> {
+    if arguments.len() != function.arguments.len() {
+        return Err(RuntimeError::ArgumentCount {
+            function: name.to_owned(),
+            expected: function.arguments.len(),
+            found: arguments.len(),
+        });
+    }
+    let stack_exhausted = stacker::remaining_stack().is_some_and(|stack| stack < MIN_CALL_STACK);
+    if state.frames.len() == MAX_CALL_DEPTH || stack_exhausted {
+        return Err(RuntimeError::CallDepthExceeded(name.to_owned()));
+    }
+    let mut frame: HashMap<_, _> = function.arguments.iter().copied().zip(arguments).collect();
+    if let Some(receiver) = &mut receiver {
+        frame.insert("self", std::mem::replace(*receiver, Value::None));
+    }
+    state.frames.push(frame);
+    let signal = run_statements(&function.statements, state);
+    if let (Some(receiver), Some(mut frame)) = (receiver, state.frames.pop()) {
+        *receiver = frame.remove("self").unwrap_or(Value::None);
+    }
+    match signal? {
+        Signal::Return(value) => Ok(value),
+        _ => Ok(Value::None),
+    }
+}
+
+fn method_call<'a>(
+    object: &'a Expression<'a>,
+    method: &'a str,
+    arguments: &'a [Expression<'a>],
+    state: &mut State<'a>,
+) -> Result<Value, RuntimeError> {
+    let mut path = Vec::new();
+    let (name, value) = match place_root(object) {
+        Some(name) if state.variable(name).is_some() => {
+            (Some(target_path(object, &mut path, state)?), Value::None)
+        }
+        _ => (None, evaluate(object, state)?),
+    };
+    let arguments = evaluate_all(arguments, state)?;
+    let mut receiver = match name {
+        Some(name) => load(name, &path, state)?,
+        None => value,
+    };
+    let function = match &receiver {
+        Value::Struct(instance) => state
+            .structs
+            .get(instance.name.as_str())
+            .and_then(|structure| structure.methods.iter().find(|(n, _)| *n == method)),
+        _ => None,
+    };
+    let Some((_, function)) = function else {
+        return call_method(receiver, method, arguments);
+    };
+    let value = call_function(method, function, Some(&mut receiver), arguments, state)?;
+    if let Some(name) = name {
+        store(name, &path, receiver, state)?;
+    }
+    Ok(value)
+}
+
+fn construct(name: &str, fields: &[&str], arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    check_field_count(name, fields, arguments.len())?;
+    let fields = fields.iter().map(|field| (*field).to_owned());
+    Ok(Value::Struct(Box::new(Instance {
+        name: name.to_owned(),
+        fields: fields.zip(arguments).collect(),
+    })))
+}
+
+fn check_field_count(name: &str, fields: &[&str], found: usize) -> Result<(), RuntimeError> {
+    if found != fields.len() {
+        return Err(RuntimeError::FieldCount {
+            structure: name.to_owned(),
+            expected: fields.len(),
+            found,
+        });
+    }
+    Ok(())
+}
+
+fn index_value(array: Value, index: Value) -> Result<Value, RuntimeError> {
+    match (array, index) {
+        (Value::Array(mut array), Value::Number(index)) if index.fract() == 0. => {
+            let index = checked_index(index, array.len())?;
+            Ok(array.swap_remove(index))
+        }
+        (Value::Map(map), key) => {
+            check_key(&key)?;
+            match map.get(&key) {
+                Some(value) => Ok(value.clone()),
+                None => Err(RuntimeError::MissingKey(key)),
+            }
+        }
+        (array, index) => Err(RuntimeError::InvalidIndex { array, index }),
+    }
+}
+
+fn index_place<'v>(object: &'v mut Value, index: &Value) -> Result<&'v mut Value, RuntimeError> {
+    match object {
+        Value::Array(elements) => match index {
+            Value::Number(index) if index.fract() == 0. => {
+                let index = checked_index(*index, elements.len())?;
+                Ok(&mut elements[index])
+            }
+            index => Err(RuntimeError::InvalidIndex {
+                array: Value::Array(elements.clone()),
+                index: index.clone(),
+            }),
+        },
+        Value::Map(map) => {
+            check_key(index)?;
+            map.get_mut(index)
+                .ok_or_else(|| RuntimeError::MissingKey(index.clone()))
+        }
+        object => Err(RuntimeError::InvalidIndex {
+            array: object.clone(),
+            index: index.clone(),
+        }),
+    }
+}
+
+fn check_key(key: &Value) -> Result<(), RuntimeError> {
+    match key {
+        Value::Number(number) if !number.is_nan() => Ok(()),
+        Value::Text(_) => Ok(()),
+        key => Err(RuntimeError::InvalidKey(key.clone())),
+    }
+}
+
+fn checked_index(index: f64, length: usize) -> Result<usize, RuntimeError> {
+    if index >= 0. && index < length as f64 {
+        Ok(index as usize)
+    } else {
+        Err(RuntimeError::IndexOutOfBounds { index, length })
+    }
+}
+
+fn member_value(object: Value, member: &str) -> Result<Value, RuntimeError> {
+    match (object, member) {
+        (Value::Text(text), "length") => Ok(Value::Number(text.chars().count() as f64)),
+        (Value::Array(elements), "length") => Ok(Value::Number(elements.len() as f64)),
+        (Value::Map(map), "length") => Ok(Value::Number(map.len() as f64)),
+        (Value::Struct(instance), field) => match instance.field(field) {
+            Some(value) => Ok(value.clone()),
+            None => Err(unknown_field(&instance, field)),
+        },
+        (object, member) => Err(RuntimeError::UnknownMember {
+            object,
+            member: member.to_owned(),
+        }),
+    }
+}
+
+fn member_place<'v>(object: &'v mut Value, member: &str) -> Result<&'v mut Value, RuntimeError> {
+    match object {
+        Value::Struct(instance) => match instance.fields.iter().position(|(n, _)| n == member) {
+            Some(index) => Ok(&mut instance.fields[index].1),
+            None => Err(unknown_field(instance, member)),
+        },
+        object => Err(RuntimeError::UnknownMember {
+            object: object.clone(),
+            member: member.to_owned(),
+        }),
+    }
+}
+
+fn unknown_field(instance: &Instance, field: &str) -> RuntimeError {
+    RuntimeError::UnknownField {
+        structure: instance.name.clone(),
+        field: field.to_owned(),
+    }
+}
+
+fn call_method(object: Value, method: &str, arguments: Vec<Value>) -> Result<Value, RuntimeError> {
+    match (object, method, arguments.as_slice()) {
+        (Value::Text(haystack), "starts_with", [needle]) => {
+            let Value::Text(needle) = needle else {
+                return Err(RuntimeError::InvalidArgument {
+                    method: method.to_owned(),
+                    argument: needle.clone(),
+                });
+            };
+            Ok(Value::Bool(haystack.starts_with(needle)))
+        }
+        (Value::Map(map), "keys", []) => Ok(Value::Array(map.keys().cloned().collect())),
+        (Value::Map(map), "values", []) => Ok(Value::Array(map.values().cloned().collect())),
+        (Value::Map(map), "contains", [key]) => {
+            check_key(key)?;
+            Ok(Value::Bool(map.get(key).is_some()))
+        }
+        (object, method, arguments) => Err(RuntimeError::UnknownMethod {
+            object,
+            method: method.to_owned(),
+            arguments: arguments.len(),
+        }),
+    }
+}
+
+fn evaluate_unary_operator(op: UnaryOperator, operand: Value) -> Result<Value, RuntimeError> {
+    match (op, operand) {
+        (UnaryOperator::Negate, Value::Number(number)) => Ok(Value::Number(-number)),
+        (UnaryOperator::Not, Value::Bool(bool)) => Ok(Value::Bool(!bool)),
+        (operator, operand) => Err(RuntimeError::InvalidOperand { operator, operand }),
+    }
+}
+
+fn evaluate_binary_operator(
+    op: BinaryOperator,
+    lhs: Value,
+    rhs: Value,
+) -> Result<Value, RuntimeError> {
+    if let BinaryOperator::Equal | BinaryOperator::NotEqual = op {
+        return Ok(Value::Bool(
+            (lhs == rhs) == matches!(op, BinaryOperator::Equal),
+        ));
+    }
+    evalute_binary_expression! {
+        op lhs rhs
+        Add Number Number => Number lhs + rhs,
+        Add Text Text => Text lhs + &rhs,
+        Subtract Number Number => Number lhs - rhs,
+        Multiply Number Number => Number lhs * rhs,
+        Divide Number Number => Number lhs / rhs,
+        Modulo Number Number => Number lhs.rem_euclid(rhs),
+        Less Number Number => Bool lhs < rhs,
+        Less Text Text => Bool lhs < rhs,
+        LessEqual Number Number => Bool lhs <= rhs,
+        LessEqual Text Text => Bool lhs <= rhs,
+        Greater Number Number => Bool lhs > rhs,
+        Greater Text Text => Bool lhs > rhs,
+        GreaterEqual Number Number => Bool lhs >= rhs,
+        GreaterEqual Text Text => Bool lhs >= rhs,
+        And Bool Bool => Bool lhs && rhs,
+        Or Bool Bool => Bool lhs || rhs,
     }
 }
@@ -0,0 +1,135 @@
+use crate::ast::{BinaryOperator, UnaryOperator};
+use crate::interpreter::value::Value;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug)]
+pub enum RuntimeError {
+    UndefinedVariable(String),
+    UndefinedFunction(String),
+    ArgumentCount {
+        function: String,
+        expected: usize,
+        found: usize,
+    },
+    InvalidOperand {
+        operator: UnaryOperator,
+        operand: Value,
+    },
+    InvalidOperands {
+        operator: BinaryOperator,
+        lhs: Value,
+        rhs: Value,
+    },
+    InvalidCondition(Value),
+    InvalidAssignTarget,
+    InvalidIterable(Value),
+    InvalidRange(Value),
+    InvalidIndex {
+        array: Value,
+        index: Value,
+    },
+    IndexOutOfBounds {
+        index: f64,
+        length: usize,
+    },
+    InvalidKey(Value),
+    MissingKey(Value),
+    FieldCount {
+        structure: String,
+        expected: usize,
+        found: usize,
+    },
+    UnknownField {
+        structure: String,
+        field: String,
+    },
+    DuplicateDeclaration(String),
+    UnknownMember {
+        object: Value,
+        member: String,
+    },
+    UnknownMethod {
+        object: Value,
+        method: String,
+        arguments: usize,
+    },
+    InvalidArgument {
+        method: String,
+        argument: Value,
+    },
+    DuplicateBinding(String),
+    NoMatch(Value),
+    ReturnOutsideFunction,
+    CallDepthExceeded(String),
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuntimeError::UndefinedVariable(name) => write!(f, "undefined variable {name:?}"),
+            RuntimeError::UndefinedFunction(name) => write!(f, "undefined function {name:?}"),
+            RuntimeError::ArgumentCount {
+                function,
+                expected,
+                found,
+            } => write!(
+                f,
+                "function {function:?} takes {expected} arguments but {found} were given"
+            ),
+            RuntimeError::InvalidOperand { operator, operand } => {
+                write!(f, "can't evaluate {operator:?} {operand:?}")
+            }
+            RuntimeError::InvalidOperands { operator, lhs, rhs } => {
+                write!(f, "can't evaluate {lhs:?} {operator:?} {rhs:?}")
+            }
+            RuntimeError::InvalidCondition(value) => write!(f, "can't use {value:?} as condition"),
+            RuntimeError::InvalidAssignTarget => write!(f, "can't assign to expression"),
+            RuntimeError::InvalidIterable(value) => write!(f, "can't iterate over {value:?}"),
+            RuntimeError::InvalidRange(value) => write!(f, "can't use {value:?} in range"),
+            RuntimeError::InvalidIndex { array, index } => {
+                write!(f, "can't index {array:?} with {index:?}")
+            }
+            RuntimeError::IndexOutOfBounds { index, length } => {
+                write!(f, "index {index:?} out of bounds for length {length}")
+            }
+            RuntimeError::InvalidKey(key) => write!(f, "can't use {key:?} as map key"),
+            RuntimeError::MissingKey(key) => write!(f, "key {key:?} not found in map"),
+            RuntimeError::FieldCount {
+                structure,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{structure:?} takes {expected} fields but {found} were given"
+            ),
+            RuntimeError::UnknownField { structure, field } => {
+                write!(f, "struct {structure:?} has no field {field:?}")
+            }
+            RuntimeError::DuplicateDeclaration(name) => write!(f, "{name:?} is already declared"),
+            RuntimeError::UnknownMember { object, member } => {
+                write!(f, "unknown member {member:?} of value {object:?}")
+            }
+            RuntimeError::UnknownMethod {
+                object,
+                method,
+                arguments,
+            } => write!(
+                f,
+                "unknown method {method:?} of value {object:?} with {arguments} arguments"
+            ),
+            RuntimeError::InvalidArgument { method, argument } => {
+                write!(f, "can't call method {method:?} with argument {argument:?}")
+            }
+            RuntimeError::DuplicateBinding(name) => {
+                write!(f, "{name:?} is bound more than once in pattern")
+            }
+            RuntimeError::NoMatch(value) => write!(f, "no match arm for {value:?}"),
+            RuntimeError::ReturnOutsideFunction => write!(f, "can't return in top level"),
+            RuntimeError::CallDepthExceeded(function) => {
+                write!(f, "call depth exceeded when calling function {function:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
@@ -5,7 +5,10 @@ use std::ops::Index;
 pub enum Value {
     Array(Vec<Value>),
     Bool(bool),
+    Map(Map),
+    None,
     Number(f64),
+    Struct(Box<Instance>),
     Text(String),
 }
 
@@ -14,12 +17,92 @@ impl Debug for Value {
         match self {
             Value::Array(values) => Debug::fmt(values, f),
             Value::Bool(bool) => Debug::fmt(bool, f),
+            Value::Map(map) => Debug::fmt(map, f),
+            Value::None => f.write_str("none"),
             Value::Number(number) => Debug::fmt(number, f),
+            Value::Struct(instance) => Debug::fmt(instance, f),
             Value::Text(text) => Debug::fmt(text, f),
         }
     }
 }
 
+#[derive(Clone, Default)]
+pub struct Map {
+    entries: Vec<(Value, Value)>,
+}
+
+impl Map {
+    pub fn get(&self, key: &Value) -> Option<&Value> {
+        self.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub fn get_mut(&mut self, key: &Value) -> Option<&mut Value> {
+        self.entries
+            .iter_mut()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+
+    pub fn insert(&mut self, key: Value, value: Value) {
+        match self.get_mut(&key) {
+            Some(slot) => *slot = value,
+            None => self.entries.push((key, value)),
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &Value> {
+        self.entries.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.entries.iter().map(|(_, v)| v)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Debug for Map {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(self.entries.iter().map(|(k, v)| (k, v)))
+            .finish()
+    }
+}
+
+impl PartialEq for Map {
+    fn eq(&self, rhs: &Map) -> bool {
+        self.len() == rhs.len() && self.entries.iter().all(|(k, v)| rhs.get(k) == Some(v))
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Instance {
+    pub name: String,
+    pub fields: Vec<(String, Value)>,
+}
+
+impl Instance {
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        self.fields.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+}
+
+impl Debug for Instance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct(&self.name);
+        for (name, value) in &self.fields {
+            debug.field(name, value);
+        }
+        debug.finish()
+    }
+}
+
 impl Index<usize> for Value {
     type Output = Value;
 
@@ -31,6 +114,34 @@ impl Index<usize> for Value {
     }
 }
 
+impl Index<&str> for Value {
+    type Output = Value;
+
+    fn index(&self, key: &str) -> &Value {
+        match self {
+            Value::Map(map) => map
+                .get(&Value::Text(key.to_owned()))
+                .unwrap_or_else(|| panic!("missing key {key:?}")),
+            _ => panic!("can't index {self:?}"),
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, rhs: &Value) -> bool {
+        match (self, rhs) {
+            (Value::Array(lhs), Value::Array(rhs)) => lhs == rhs,
+            (Value::Bool(lhs), Value::Bool(rhs)) => lhs == rhs,
+            (Value::Map(lhs), Value::Map(rhs)) => lhs == rhs,
+            (Value::None, Value::None) => true,
+            (Value::Number(lhs), Value::Number(rhs)) => lhs == rhs,
+            (Value::Struct(lhs), Value::Struct(rhs)) => lhs == rhs,
+            (Value::Text(lhs), Value::Text(rhs)) => lhs == rhs,
+            _ => false,
+        }
+    }
+}
+
 impl PartialEq<&[i32]> for Value {
     fn eq(&self, rhs: &&[i32]) -> bool {
         match self {
@@ -49,6 +160,12 @@ impl PartialEq<bool> for Value {
     }
 }
 
+impl PartialEq<()> for Value {
+    fn eq(&self, _: &()) -> bool {
+        matches!(self, Value::None)
+    }
+}
+
 impl PartialEq<i32> for Value {
     fn eq(&self, rhs: &i32) -> bool {
         match self {
@@ -1,141 +1,537 @@
-use crate::ast::{BinaryOperator, Expression, Function, Literal, Program, Statement};
+mod error;
+mod token;
+
+pub use crate::parser::error::ParseError;
+
+use crate::ast::{
+    Arm, BinaryOperator, Enum, Expression, ExpressionKind, Function, Iterable, Literal, Node,
+    Pattern, PatternKind, Program, Range, Span, Statement, StatementKind, Struct, UnaryOperator,
+};
+use crate::parser::error::failure;
+use crate::parser::token::{
+    bracket_space, declared_identifier, identifier, indentation, keyword, line_ending, list,
+    reserved_keyword, space, take_comments, trivia, RESERVED_KEYWORD,
+};
 use nom::branch::alt;
-use nom::bytes::complete::{tag, take_until, take_while1};
-use nom::character::complete::{char, digit1, newline};
-use nom::combinator::cut;
-use nom::error::Error;
-use nom::multi::{many0, separated_list0};
-use nom::sequence::{delimited, preceded};
-use nom::{IResult, Parser};
-
-pub fn parse(code: &str) -> Program {
-    let (code, program) = program(code).unwrap();
-    assert_eq!(code, "");
-    program
-}
-
-fn program(code: &str) -> IResult<&str, Program> {
-    let (code, statements) = separated_list0(newline, cut(statement))(code)?;
-    Ok((code, Program { statements }))
-}
-
-fn statement(code: &str) -> IResult<&str, Statement> {
-    statement1(code)
-}
-
-fn statement1(code: &str) -> IResult<&str, Statement> {
-    alt((function, while_loop, if_statement, statement0))(code)
-}
-
-fn statement0(code: &str) -> IResult<&str, Statement> {
-    alt((return_statement, assign))(code)
-}
-
-fn function(code: &str) -> IResult<&str, Statement> {
-    let (code, _) = tag("func ")(code)?;
-    let (code, name) = identifier(code)?;
-    let (code, arguments) =
-        delimited(char('('), separated_list0(tag(", "), identifier), char(')'))(code)?;
-    let (code, statements) = many0(preceded(tag("\n    "), statement0))(code)?;
+use nom::bytes::complete::{tag, take_till, take_until, take_while, take_while_m_n};
+use nom::character::complete::{char, none_of, one_of, satisfy};
+use nom::combinator::{cut, map, map_opt, not, opt, peek, recognize, value};
+use nom::error::{context, ContextError, ErrorKind, ParseError as _, VerboseError};
+use nom::multi::{many0, many1, separated_list1};
+use nom::sequence::{delimited, pair, preceded, tuple};
+use nom::Slice;
+use nom_locate::LocatedSpan;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+type Comments = Rc<RefCell<BTreeMap<usize, Span>>>;
+
+type Input<'a> = LocatedSpan<&'a str, Comments>;
+
+type IResult<'a, O> = nom::IResult<Input<'a>, O, VerboseError<Input<'a>>>;
+
+pub fn parse(code: &str) -> Result<Program<'_>, ParseError> {
+    match program(Input::new_extra(code, Comments::default())) {
+        Ok((rest, program)) if rest.is_empty() => Ok(program),
+        Ok((rest, _)) => Err(ParseError::new(
+            code,
+            rest,
+            "expected end of line".to_owned(),
+        )),
+        Err(nom::Err::Error(error) | nom::Err::Failure(error)) => {
+            Err(ParseError::from_nom(code, error))
+        }
+        Err(nom::Err::Incomplete(_)) => unreachable!(),
+    }
+}
+
+fn program(code: Input<'_>) -> IResult<'_, Program<'_>> {
+    let start = code.clone();
+    let (code, _) = many0(pair(trivia, line_ending))(code)?;
+    let (code, statements) = match line_content(code.clone()) {
+        Ok(_) => statements(code, Scope::default())?,
+        Err(_) => (code, Vec::new()),
+    };
+    let (code, _) = pair(many0(pair(trivia, line_ending)), trivia)(code)?;
+    let program = Program {
+        statements,
+        comments: take_comments(start),
+    };
+    Ok((code, program))
+}
+
+fn node<'a, K>(
+    mut parser: impl FnMut(Input<'a>) -> IResult<'a, K>,
+) -> impl FnMut(Input<'a>) -> IResult<'a, Node<K>> {
+    move |code| {
+        let start = code.clone();
+        let (code, kind) = parser(code)?;
+        let span = span(&start, &code);
+        Ok((code, Node { kind, span }))
+    }
+}
+
+fn span(start: &Input, end: &Input) -> Span {
+    Span {
+        start: start.location_offset(),
+        end: end.location_offset(),
+        line: start.location_line() as usize,
+        column: start.get_utf8_column(),
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Scope<'a> {
+    indent: &'a str,
+    in_loop: bool,
+}
+
+fn statements<'a>(code: Input<'a>, scope: Scope<'a>) -> IResult<'a, Vec<Statement<'a>>> {
+    separated_list1(
+        |code| line(code, scope.indent),
+        cut(|code| statement(code, scope)),
+    )(code)
+}
+
+fn block<'a>(code: Input<'a>, scope: Scope<'a>) -> IResult<'a, Vec<Statement<'a>>> {
+    indented_block(code, scope, statements)
+}
+
+fn indented_block<'a, O>(
+    code: Input<'a>,
+    scope: Scope<'a>,
+    body: impl FnOnce(Input<'a>, Scope<'a>) -> IResult<'a, Vec<O>>,
+) -> IResult<'a, Vec<O>> {
+    match nested_indent(code.clone(), scope) {
+        Some((rest, indent)) => body(rest, Scope { indent, ..scope }),
+        None => failure(code, "expected indented block"),
+    }
+}
+
+fn optional_indented_block<'a, O>(
+    code: Input<'a>,
+    scope: Scope<'a>,
+    body: impl FnOnce(Input<'a>, Scope<'a>) -> IResult<'a, Vec<O>>,
+) -> IResult<'a, Vec<O>> {
+    match nested_indent(code.clone(), scope) {
+        Some((rest, indent)) => body(rest, Scope { indent, ..scope }),
+        None => Ok((code, Vec::new())),
+    }
+}
+
+fn nested_indent<'a>(code: Input<'a>, scope: Scope<'a>) -> Option<(Input<'a>, &'a str)> {
+    let (rest, indent) = preceded(next_line, indentation)(code).ok()?;
+    if indent.len() <= scope.indent.len() || !indent.starts_with(scope.indent) {
+        return None;
+    }
+    Some((rest, indent))
+}
+
+fn line<'a>(code: Input<'a>, indent: &'a str) -> IResult<'a, ()> {
+    let (code, _) = next_line(code)?;
+    let (rest, line_indent) = indentation(code.clone())?;
+    if line_indent == indent {
+        Ok((rest, ()))
+    } else if line_indent.starts_with(indent) {
+        let error = VerboseError::add_context(
+            code.clone(),
+            "matching indentation",
+            VerboseError::from_error_kind(code, ErrorKind::Space),
+        );
+        Err(nom::Err::Failure(error))
+    } else {
+        Err(nom::Err::Error(VerboseError::from_error_kind(
+            code,
+            ErrorKind::Space,
+        )))
+    }
+}
+
+fn next_line(code: Input<'_>) -> IResult<'_, ()> {
+    let (code, _) = many1(pair(trivia, line_ending))(code)?;
+    let (code, _) = line_content(code)?;
+    Ok((code, ()))
+}
+
+fn line_content(code: Input<'_>) -> IResult<'_, ()> {
+    let (_, _) = peek(pair(indentation, none_of("#")))(code.clone())?;
+    Ok((code, ()))
+}
+
+fn statement<'a>(code: Input<'a>, scope: Scope<'a>) -> IResult<'a, Statement<'a>> {
+    context("statement", |code| statement1(code, scope))(code)
+}
+
+fn statement1<'a>(code: Input<'a>, scope: Scope<'a>) -> IResult<'a, Statement<'a>> {
+    alt((
+        context("function declaration", node(|code| function(code, scope))),
+        context("while loop", node(|code| while_loop(code, scope))),
+        context("for loop", node(|code| for_loop(code, scope))),
+        context("if statement", node(|code| if_statement(code, scope))),
+        context("match statement", node(|code| match_statement(code, scope))),
+        context("struct declaration", node(|code| structure(code, scope))),
+        context("enum declaration", node(|code| enumeration(code, scope))),
+        node(|code| loop_control(code, scope)),
+        statement0,
+    ))(code)
+}
+
+fn statement0(code: Input<'_>) -> IResult<'_, Statement<'_>> {
+    alt((
+        context("return statement", node(return_statement)),
+        context("assignment", node(assign)),
+        context("compound assignment", node(compound_assign)),
+        context("expression statement", node(expression_statement)),
+    ))(code)
+}
+
+fn function<'a>(code: Input<'a>, scope: Scope<'a>) -> IResult<'a, StatementKind<'a>> {
+    let (code, (name, function)) = function_declaration(code, scope, &[])?;
+    Ok((code, StatementKind::Function(name, function)))
+}
+
+fn function_declaration<'a>(
+    code: Input<'a>,
+    scope: Scope<'a>,
+    implicit: &'static [&'static str],
+) -> IResult<'a, (&'a str, Function<'a>)> {
+    let (code, _) = pair(keyword("func"), space)(code)?;
+    let (code, name) = cut(declared_identifier)(code)?;
+    let (code, arguments) = cut(names("duplicate parameter", implicit))(code)?;
+    let scope = Scope {
+        in_loop: false,
+        ..scope
+    };
+    let (code, statements) = block(code, scope)?;
     let function = Function {
         arguments,
         statements,
     };
-    Ok((code, Statement::Function(name, function)))
-}
-
-fn while_loop(code: &str) -> IResult<&str, Statement> {
-    let (code, _) = tag("while ")(code)?;
-    let (code, condition) = expression(code)?;
-    let (code, statements) = many0(preceded(tag("\n    "), statement0))(code)?;
-    Ok((code, Statement::While(condition, statements)))
-}
-
-fn if_statement(code: &str) -> IResult<&str, Statement> {
-    let (code, _) = tag("if ")(code)?;
-    let (code, condition) = expression(code)?;
-    let (code, statements) = many0(preceded(tag("\n    "), statement0))(code)?;
-    Ok((code, Statement::If(condition, statements)))
-}
-
-fn return_statement(code: &str) -> IResult<&str, Statement> {
-    let (code, _) = tag("return ")(code)?;
-    let (code, expression) = expression(code)?;
-    Ok((code, Statement::Return(expression)))
-}
-
-fn assign(code: &str) -> IResult<&str, Statement> {
-    let (code, identifier) = identifier(code)?;
-    let (code, _) = tag(" = ")(code)?;
-    let (code, expression) = expression(code)?;
-    Ok((code, Statement::Assign(identifier, expression)))
-}
-
-fn identifier(code: &str) -> IResult<&str, &str> {
-    take_while1(|c: char| c.is_ascii_lowercase() || c == '_')(code)
+    Ok((code, (name, function)))
 }
 
-fn expression(code: &str) -> IResult<&str, Expression> {
-    expression5(code)
+fn structure<'a>(code: Input<'a>, scope: Scope<'a>) -> IResult<'a, StatementKind<'a>> {
+    let (code, _) = pair(keyword("struct"), space)(code)?;
+    let (code, name) = cut(declared_identifier)(code)?;
+    let (code, fields) = cut(names("duplicate field", &[]))(code)?;
+    let (code, methods) = optional_indented_block(code, scope, methods)?;
+    Ok((
+        code,
+        StatementKind::Struct(name, Struct { fields, methods }),
+    ))
 }
 
-fn expression5(code: &str) -> IResult<&str, Expression> {
-    alt((logic, expression4))(code)
+fn names<'a>(
+    duplicate: &'static str,
+    implicit: &'static [&'static str],
+) -> impl FnMut(Input<'a>) -> IResult<'a, Vec<&'a str>> {
+    move |code| {
+        let (rest, names) = list('(', node(declared_identifier), ')')(code.clone())?;
+        for (i, name) in names.iter().enumerate() {
+            let previous = names[..i].iter().map(|name| name.kind);
+            if implicit
+                .iter()
+                .copied()
+                .chain(previous)
+                .any(|other| other == name.kind)
+            {
+                let offset = name.span.start - code.location_offset();
+                return failure(code.slice(offset..), duplicate);
+            }
+        }
+        Ok((rest, names.into_iter().map(|name| name.kind).collect()))
+    }
+}
+
+fn methods<'a>(code: Input<'a>, scope: Scope<'a>) -> IResult<'a, Vec<(&'a str, Function<'a>)>> {
+    let start = code.clone();
+    let (code, methods) = separated_list1(
+        |code| line(code, scope.indent),
+        cut(context(
+            "method declaration",
+            node(|code| function_declaration(code, scope, &["self"])),
+        )),
+    )(code)?;
+    for (i, method) in methods.iter().enumerate() {
+        if methods[..i]
+            .iter()
+            .any(|other| other.kind.0 == method.kind.0)
+        {
+            let offset = method.span.start - start.location_offset();
+            return failure(start.slice(offset..), "duplicate method");
+        }
+    }
+    Ok((
+        code,
+        methods.into_iter().map(|method| method.kind).collect(),
+    ))
 }
 
-fn expression4(code: &str) -> IResult<&str, Expression> {
-    alt((comparison, expression3))(code)
+fn enumeration<'a>(code: Input<'a>, scope: Scope<'a>) -> IResult<'a, StatementKind<'a>> {
+    let (code, _) = pair(keyword("enum"), space)(code)?;
+    let (code, name) = cut(declared_identifier)(code)?;
+    let (code, variants) = indented_block(code, scope, variants)?;
+    Ok((code, StatementKind::Enum(name, Enum { variants })))
 }
 
-fn expression3(code: &str) -> IResult<&str, Expression> {
-    alt((multiplication, expression2))(code)
+fn variants<'a>(code: Input<'a>, scope: Scope<'a>) -> IResult<'a, Vec<(&'a str, Vec<&'a str>)>> {
+    separated_list1(
+        |code| line(code, scope.indent),
+        cut(context("variant", variant)),
+    )(code)
 }
 
-fn expression2(code: &str) -> IResult<&str, Expression> {
-    alt((addition, expression1))(code)
+fn variant(code: Input<'_>) -> IResult<'_, (&str, Vec<&str>)> {
+    let (code, name) = declared_identifier(code)?;
+    let (code, fields) = opt(names("duplicate field", &[]))(code)?;
+    Ok((code, (name, fields.unwrap_or_default())))
 }
 
-fn expression1(code: &str) -> IResult<&str, Expression> {
-    alt((method, member, index, call, expression0))(code)
+fn match_statement<'a>(code: Input<'a>, scope: Scope<'a>) -> IResult<'a, StatementKind<'a>> {
+    let (code, _) = pair(keyword("match"), space)(code)?;
+    let (code, subject) = cut(expression)(code)?;
+    let (code, arms) = indented_block(code, scope, arms)?;
+    Ok((code, StatementKind::Match(subject, arms)))
 }
 
-fn expression0(code: &str) -> IResult<&str, Expression> {
-    alt((array, literal, variable))(code)
+fn arms<'a>(code: Input<'a>, scope: Scope<'a>) -> IResult<'a, Vec<Node<Arm<'a>>>> {
+    separated_list1(
+        |code| line(code, scope.indent),
+        cut(context("match arm", node(|code| arm(code, scope)))),
+    )(code)
 }
 
-fn binary_expression<'a>(
-    mut subexpression: impl Parser<&'a str, Expression<'a>, Error<&'a str>>,
-    operator: impl Parser<&'a str, BinaryOperator, Error<&'a str>>,
-    code: &'a str,
-) -> IResult<&'a str, Expression> {
-    let (code, lhs) = subexpression.parse(code)?;
-    let (code, op) = delimited(char(' '), operator, char(' '))(code)?;
-    let (code, rhs) = subexpression.parse(code)?;
-    Ok((
-        code,
-        Expression::BinaryOperator(Box::new(lhs), op, Box::new(rhs)),
-    ))
+fn arm<'a>(code: Input<'a>, scope: Scope<'a>) -> IResult<'a, Arm<'a>> {
+    let (code, pattern) = pattern(code)?;
+    let guard_keyword = tuple((space, keyword("if"), space));
+    let (code, guard) = opt(preceded(guard_keyword, cut(expression)))(code)?;
+    let (code, statements) = block(code, scope)?;
+    let arm = Arm {
+        pattern,
+        guard,
+        statements,
+    };
+    Ok((code, arm))
+}
+
+fn pattern(code: Input<'_>) -> IResult<'_, Pattern<'_>> {
+    context(
+        "pattern",
+        node(alt((
+            map(keyword("_"), |_| PatternKind::Wildcard),
+            map(pattern_literal, PatternKind::Literal),
+            map(list('[', pattern, ']'), PatternKind::Array),
+            map(
+                pair(identifier, list('(', pattern, ')')),
+                |(name, fields)| PatternKind::Variant(name, fields),
+            ),
+            map(declared_identifier, PatternKind::Identifier),
+        ))),
+    )(code)
+}
+
+fn pattern_literal(code: Input<'_>) -> IResult<'_, Literal<'_>> {
+    alt((
+        literal_bool,
+        literal_none,
+        literal_number,
+        context("text", literal_text),
+        map_opt(
+            preceded(char('-'), literal_number),
+            |literal| match literal {
+                Literal::Number(number) => Some(Literal::Number(-number)),
+                _ => None,
+            },
+        ),
+    ))(code)
+}
+
+fn while_loop<'a>(code: Input<'a>, scope: Scope<'a>) -> IResult<'a, StatementKind<'a>> {
+    let (code, _) = pair(keyword("while"), space)(code)?;
+    let (code, condition) = cut(expression)(code)?;
+    let scope = Scope {
+        in_loop: true,
+        ..scope
+    };
+    let (code, statements) = block(code, scope)?;
+    Ok((code, StatementKind::While(condition, statements)))
+}
+
+fn for_loop<'a>(code: Input<'a>, scope: Scope<'a>) -> IResult<'a, StatementKind<'a>> {
+    let (code, _) = pair(keyword("for"), space)(code)?;
+    let (code, variable) = cut(declared_identifier)(code)?;
+    let (code, _) = cut(tuple((space, keyword("in"), space)))(code)?;
+    let (code, iterable) = cut(iterable)(code)?;
+    let scope = Scope {
+        in_loop: true,
+        ..scope
+    };
+    let (code, statements) = block(code, scope)?;
+    Ok((code, StatementKind::For(variable, iterable, statements)))
 }
 
-fn logic(code: &str) -> IResult<&str, Expression> {
-    binary_expression(expression4, logic_operator, code)
+fn iterable(code: Input<'_>) -> IResult<'_, Iterable<'_>> {
+    let (code, start) = expression(code)?;
+    let range_operator = preceded(space, alt((tag("..="), tag(".."))));
+    let (code, range) = opt(pair(range_operator, preceded(space, cut(expression))))(code)?;
+    let Some((operator, end)) = range else {
+        return Ok((code, Iterable::Expression(start)));
+    };
+    let step_keyword = tuple((space, keyword("step"), space));
+    let (code, step) = opt(preceded(step_keyword, cut(expression)))(code)?;
+    let range = Range {
+        start,
+        end,
+        inclusive: *operator.fragment() == "..=",
+        step,
+    };
+    Ok((code, Iterable::Range(range)))
 }
 
-fn comparison(code: &str) -> IResult<&str, Expression> {
-    binary_expression(expression3, comparison_operator, code)
+fn if_statement<'a>(code: Input<'a>, scope: Scope<'a>) -> IResult<'a, StatementKind<'a>> {
+    let (code, _) = pair(keyword("if"), space)(code)?;
+    let (code, condition) = cut(expression)(code)?;
+    let (code, statements) = block(code, scope)?;
+    let (code, mut branches) = many0(|code| elif_branch(code, scope))(code)?;
+    branches.insert(0, (condition, statements));
+    let (code, otherwise) = opt(|code| else_branch(code, scope))(code)?;
+    Ok((code, StatementKind::If(branches, otherwise)))
 }
 
-fn multiplication(code: &str) -> IResult<&str, Expression> {
-    binary_expression(expression2, multiplicative_operator, code)
+fn elif_branch<'a>(
+    code: Input<'a>,
+    scope: Scope<'a>,
+) -> IResult<'a, (Expression<'a>, Vec<Statement<'a>>)> {
+    let (code, _) = line(code, scope.indent)?;
+    let (code, _) = pair(keyword("elif"), space)(code)?;
+    let (code, condition) = cut(expression)(code)?;
+    let (code, statements) = block(code, scope)?;
+    Ok((code, (condition, statements)))
 }
 
-fn addition(code: &str) -> IResult<&str, Expression> {
-    binary_expression(expression1, additive_operator, code)
+fn else_branch<'a>(code: Input<'a>, scope: Scope<'a>) -> IResult<'a, Vec<Statement<'a>>> {
+    let (code, _) = line(code, scope.indent)?;
+    let (code, _) = keyword("else")(code)?;
+    block(code, scope)
 }
 
-fn logic_operator(code: &str) -> IResult<&str, BinaryOperator> {
-    let (code, op) = alt((tag("and"), tag("or")))(code)?;
-    let op = match op {
+fn loop_control<'a>(code: Input<'a>, scope: Scope<'a>) -> IResult<'a, StatementKind<'a>> {
+    let (rest, statement) = alt((keyword("break"), keyword("continue")))(code.clone())?;
+    let (statement, message) = match *statement.fragment() {
+        "break" => (StatementKind::Break, "'break' outside loop"),
+        "continue" => (StatementKind::Continue, "'continue' outside loop"),
+        _ => unreachable!(),
+    };
+    if !scope.in_loop {
+        return failure(code, message);
+    }
+    Ok((rest, statement))
+}
+
+fn return_statement(code: Input<'_>) -> IResult<'_, StatementKind<'_>> {
+    let (code, _) = pair(keyword("return"), space)(code)?;
+    let (code, expression) = opt(expression)(code)?;
+    Ok((code, StatementKind::Return(expression)))
+}
+
+fn assign(code: Input<'_>) -> IResult<'_, StatementKind<'_>> {
+    let (code, target) = assign_target(code)?;
+    let (code, _) = tuple((space, char('='), not(char('=')), space))(code)?;
+    let (code, expression) = cut(expression)(code)?;
+    Ok((code, StatementKind::Assign(target, expression)))
+}
+
+fn compound_assign(code: Input<'_>) -> IResult<'_, StatementKind<'_>> {
+    let (code, target) = assign_target(code)?;
+    let (code, _) = space(code)?;
+    let (code, op) = alt((additive_operator, multiplicative_operator))(code)?;
+    let (code, _) = pair(char('='), space)(code)?;
+    let (code, expression) = cut(expression)(code)?;
+    Ok((code, StatementKind::CompoundAssign(target, op, expression)))
+}
+
+fn assign_target(code: Input<'_>) -> IResult<'_, Expression<'_>> {
+    let assign_operator = tuple((
+        space,
+        opt(alt((additive_operator, multiplicative_operator))),
+        char('='),
+        not(char('=')),
+    ));
+    if pair(reserved_keyword, assign_operator)(code.clone()).is_ok() {
+        return failure(code, RESERVED_KEYWORD);
+    }
+    let start = code.clone();
+    let (code, variable) = node(variable)(code)?;
+    suffixes(start, code, variable, alt((member, index)))
+}
+
+fn expression_statement(code: Input<'_>) -> IResult<'_, StatementKind<'_>> {
+    let (code, expression) = expression(code)?;
+    Ok((code, StatementKind::Expression(expression)))
+}
+
+fn expression(code: Input<'_>) -> IResult<'_, Expression<'_>> {
+    context("expression", |code| binary_expression(code, 0, false))(code)
+}
+
+fn bracketed_expression(code: Input<'_>) -> IResult<'_, Expression<'_>> {
+    context("expression", |code| binary_expression(code, 0, true))(code)
+}
+
+fn binary_expression(
+    code: Input<'_>,
+    min_precedence: u8,
+    bracketed: bool,
+) -> IResult<'_, Expression<'_>> {
+    let space = if bracketed { bracket_space } else { space };
+    let start = code.clone();
+    let (mut code, mut lhs) = unary_expression(code, bracketed)?;
+    while let (rest, Some(op)) = opt(delimited(space, binary_operator, space))(code.clone())? {
+        let precedence = precedence(op);
+        if precedence < min_precedence {
+            break;
+        }
+        let (rest, rhs) = cut(context("expression", |code| {
+            binary_expression(code, precedence + 1, bracketed)
+        }))(rest)?;
+        let kind = ExpressionKind::BinaryOperator(Box::new(lhs), op, Box::new(rhs));
+        lhs = Node {
+            kind,
+            span: span(&start, &rest),
+        };
+        code = rest;
+    }
+    Ok((code, lhs))
+}
+
+fn precedence(op: BinaryOperator) -> u8 {
+    match op {
+        BinaryOperator::Or => 1,
+        BinaryOperator::And => 2,
+        BinaryOperator::Equal | BinaryOperator::NotEqual => 4,
+        BinaryOperator::Less
+        | BinaryOperator::LessEqual
+        | BinaryOperator::Greater
+        | BinaryOperator::GreaterEqual => 5,
+        BinaryOperator::Add | BinaryOperator::Subtract => 6,
+        BinaryOperator::Multiply | BinaryOperator::Divide | BinaryOperator::Modulo => 7,
+    }
+}
+
+fn binary_operator(code: Input<'_>) -> IResult<'_, BinaryOperator> {
+    alt((
+        logic_operator,
+        comparison_operator,
+        additive_operator,
+        multiplicative_operator,
+    ))(code)
+}
+
+fn logic_operator(code: Input<'_>) -> IResult<'_, BinaryOperator> {
+    let (code, op) = alt((keyword("and"), keyword("or")))(code)?;
+    let op = match *op.fragment() {
         "and" => BinaryOperator::And,
         "or" => BinaryOperator::Or,
         _ => unreachable!(),
@@ -143,27 +539,39 @@ fn logic_operator(code: &str) -> IResult<&str, BinaryOperator> {
     Ok((code, op))
 }
 
-fn comparison_operator(code: &str) -> IResult<&str, BinaryOperator> {
-    let (code, op) = alt((tag("=="), tag("!=")))(code)?;
-    let op = match op {
+fn comparison_operator(code: Input<'_>) -> IResult<'_, BinaryOperator> {
+    let (code, op) = alt((
+        tag("=="),
+        tag("!="),
+        tag("<="),
+        tag(">="),
+        tag("<"),
+        tag(">"),
+    ))(code)?;
+    let op = match *op.fragment() {
         "==" => BinaryOperator::Equal,
         "!=" => BinaryOperator::NotEqual,
+        "<=" => BinaryOperator::LessEqual,
+        ">=" => BinaryOperator::GreaterEqual,
+        "<" => BinaryOperator::Less,
+        ">" => BinaryOperator::Greater,
         _ => unreachable!(),
     };
     Ok((code, op))
 }
 
-fn multiplicative_operator(code: &str) -> IResult<&str, BinaryOperator> {
-    let (code, op) = alt((char('*'), char('/')))(code)?;
+fn multiplicative_operator(code: Input<'_>) -> IResult<'_, BinaryOperator> {
+    let (code, op) = alt((char('*'), char('/'), char('%')))(code)?;
     let op = match op {
         '*' => BinaryOperator::Multiply,
         '/' => BinaryOperator::Divide,
+        '%' => BinaryOperator::Modulo,
         _ => unreachable!(),
     };
     Ok((code, op))
 }
 
-fn additive_operator(code: &str) -> IResult<&str, BinaryOperator> {
+fn additive_operator(code: Input<'_>) -> IResult<'_, BinaryOperator> {
     let (code, op) = alt((char('+'), char('-')))(code)?;
     let op = match op {
         '+' => BinaryOperator::Add,
@@ -173,65 +581,256 @@ fn additive_operator(code: &str) -> IResult<&str, BinaryOperator> {
     Ok((code, op))
 }
 
-fn array(code: &str) -> IResult<&str, Expression> {
-    let (code, elements) =
-        delimited(char('['), separated_list0(tag(", "), expression), char(']'))(code)?;
-    Ok((code, Expression::Array(elements)))
+fn unary_expression(code: Input<'_>, bracketed: bool) -> IResult<'_, Expression<'_>> {
+    alt((
+        node(|code| negation(code, bracketed)),
+        node(|code| logical_not(code, bracketed)),
+        postfix_expression,
+    ))(code)
+}
+
+fn negation(code: Input<'_>, bracketed: bool) -> IResult<'_, ExpressionKind<'_>> {
+    let space = if bracketed { bracket_space } else { space };
+    let (code, _) = pair(char('-'), space)(code)?;
+    let (code, operand) = cut(context("expression", |code| {
+        unary_expression(code, bracketed)
+    }))(code)?;
+    let kind = ExpressionKind::UnaryOperator(UnaryOperator::Negate, Box::new(operand));
+    Ok((code, kind))
+}
+
+fn logical_not(code: Input<'_>, bracketed: bool) -> IResult<'_, ExpressionKind<'_>> {
+    let space = if bracketed { bracket_space } else { space };
+    let (code, _) = pair(keyword("not"), space)(code)?;
+    let (code, operand) = cut(context("expression", |code| {
+        binary_expression(code, precedence(BinaryOperator::Equal), bracketed)
+    }))(code)?;
+    let kind = ExpressionKind::UnaryOperator(UnaryOperator::Not, Box::new(operand));
+    Ok((code, kind))
+}
+
+fn postfix_expression(code: Input<'_>) -> IResult<'_, Expression<'_>> {
+    let start = code.clone();
+    let (code, expression) = primary_expression(code)?;
+    suffixes(start, code, expression, alt((method, member, index)))
+}
+
+fn suffixes<'a>(
+    start: Input<'a>,
+    mut code: Input<'a>,
+    mut expression: Expression<'a>,
+    mut suffix: impl FnMut(Input<'a>) -> IResult<'a, Suffix<'a>>,
+) -> IResult<'a, Expression<'a>> {
+    while let (rest, Some(suffix)) = opt(&mut suffix)(code.clone())? {
+        let object = Box::new(expression);
+        let kind = match suffix {
+            Suffix::Method(method, arguments) => {
+                ExpressionKind::MethodCall(object, method, arguments)
+            }
+            Suffix::Member(member) => ExpressionKind::Member(object, member),
+            Suffix::Index(index) => ExpressionKind::Index(object, Box::new(index)),
+        };
+        expression = Node {
+            kind,
+            span: span(&start, &rest),
+        };
+        code = rest;
+    }
+    Ok((code, expression))
+}
+
+enum Suffix<'a> {
+    Method(&'a str, Vec<Expression<'a>>),
+    Member(&'a str),
+    Index(Expression<'a>),
+}
+
+fn method(code: Input<'_>) -> IResult<'_, Suffix<'_>> {
+    let (code, _) = char('.')(code)?;
+    let (code, method) = identifier(code)?;
+    let (code, arguments) = call_arguments(code)?;
+    Ok((code, Suffix::Method(method, arguments)))
 }
 
-fn literal(code: &str) -> IResult<&str, Expression> {
-    let (code, literal) = alt((literal_bool, literal_number, literal_text))(code)?;
-    Ok((code, Expression::Literal(literal)))
+fn member(code: Input<'_>) -> IResult<'_, Suffix<'_>> {
+    let (code, _) = char('.')(code)?;
+    let (code, member) = identifier(code)?;
+    Ok((code, Suffix::Member(member)))
 }
 
-fn literal_bool(code: &str) -> IResult<&str, Literal> {
-    let (code, value) = alt((tag("true"), tag("false")))(code)?;
-    Ok((code, Literal::Bool(value == "true")))
+fn index(code: Input<'_>) -> IResult<'_, Suffix<'_>> {
+    let (code, index) = delimited(
+        pair(char('['), bracket_space),
+        cut(bracketed_expression),
+        cut(pair(bracket_space, char(']'))),
+    )(code)?;
+    Ok((code, Suffix::Index(index)))
 }
 
-fn literal_number(code: &str) -> IResult<&str, Literal> {
-    let (code, number) = digit1(code)?;
-    Ok((code, Literal::Number(number.parse().unwrap())))
+fn primary_expression(code: Input<'_>) -> IResult<'_, Expression<'_>> {
+    alt((
+        parenthesized,
+        context("array", node(array)),
+        context("map", node(map_expression)),
+        node(literal),
+        context("call", node(call)),
+        node(variable),
+    ))(code)
 }
 
-fn literal_text(code: &str) -> IResult<&str, Literal> {
-    let (code, text) = delimited(char('"'), take_until("\""), char('"'))(code)?;
-    Ok((code, Literal::Text(text)))
+fn parenthesized(code: Input<'_>) -> IResult<'_, Expression<'_>> {
+    delimited(
+        pair(char('('), bracket_space),
+        cut(bracketed_expression),
+        cut(pair(bracket_space, char(')'))),
+    )(code)
 }
 
-fn variable(code: &str) -> IResult<&str, Expression> {
-    let (code, identifier) = identifier(code)?;
-    Ok((code, Expression::Variable(identifier)))
+fn array(code: Input<'_>) -> IResult<'_, ExpressionKind<'_>> {
+    let (code, elements) = list('[', bracketed_expression, ']')(code)?;
+    Ok((code, ExpressionKind::Array(elements)))
 }
 
-fn method(code: &str) -> IResult<&str, Expression> {
-    let (code, object) = expression0(code)?;
-    let (code, _) = char('.')(code)?;
-    let (code, method) = identifier(code)?;
-    let (code, arguments) = call_arguments(code)?;
-    let method_call = Expression::MethodCall(Box::new(object), method, arguments);
-    Ok((code, method_call))
+fn map_expression(code: Input<'_>) -> IResult<'_, ExpressionKind<'_>> {
+    let (code, entries) = list('{', map_entry, '}')(code)?;
+    Ok((code, ExpressionKind::Map(entries)))
 }
 
-fn member(code: &str) -> IResult<&str, Expression> {
-    let (code, object) = expression0(code)?;
-    let (code, _) = char('.')(code)?;
-    let (code, member) = identifier(code)?;
-    Ok((code, Expression::Member(Box::new(object), member)))
+fn map_entry(code: Input<'_>) -> IResult<'_, (Expression<'_>, Expression<'_>)> {
+    let (code, key) = bracketed_expression(code)?;
+    let (code, _) = cut(tuple((bracket_space, char(':'), bracket_space)))(code)?;
+    let (code, value) = cut(bracketed_expression)(code)?;
+    Ok((code, (key, value)))
 }
 
-fn index(code: &str) -> IResult<&str, Expression> {
-    let (code, array) = expression0(code)?;
-    let (code, index) = delimited(char('['), expression, char(']'))(code)?;
-    Ok((code, Expression::Index(Box::new(array), Box::new(index))))
+fn literal(code: Input<'_>) -> IResult<'_, ExpressionKind<'_>> {
+    let (code, literal) = alt((
+        literal_bool,
+        literal_none,
+        literal_number,
+        context("text", literal_text),
+    ))(code)?;
+    Ok((code, ExpressionKind::Literal(literal)))
+}
+
+fn literal_bool(code: Input<'_>) -> IResult<'_, Literal<'_>> {
+    let (code, value) = alt((keyword("true"), keyword("false")))(code)?;
+    Ok((code, Literal::Bool(*value.fragment() == "true")))
+}
+
+fn literal_none(code: Input<'_>) -> IResult<'_, Literal<'_>> {
+    let (code, _) = keyword("none")(code)?;
+    Ok((code, Literal::None))
+}
+
+fn literal_number(code: Input<'_>) -> IResult<'_, Literal<'_>> {
+    context(
+        "number",
+        alt((literal_radix_number, literal_decimal_number)),
+    )(code)
+}
+
+fn literal_radix_number(code: Input<'_>) -> IResult<'_, Literal<'_>> {
+    let (code, radix) = alt((
+        value(16, tag("0x")),
+        value(8, tag("0o")),
+        value(2, tag("0b")),
+    ))(code)?;
+    let (code, digits) = cut(context("digit", digits(|c| c.is_digit(radix))))(code)?;
+    let number = digits
+        .chars()
+        .filter_map(|c| c.to_digit(radix))
+        .fold(0., |number, digit| number * radix as f64 + digit as f64);
+    Ok((code, Literal::Number(number)))
+}
+
+fn literal_decimal_number(code: Input<'_>) -> IResult<'_, Literal<'_>> {
+    let (code, number) = recognize(tuple((
+        decimal_digits,
+        opt(pair(char('.'), decimal_digits)),
+        opt(tuple((one_of("eE"), opt(one_of("+-")), decimal_digits))),
+    )))(code)?;
+    let number: String = number.chars().filter(|c| *c != '_').collect();
+    Ok((code, Literal::Number(number.parse().unwrap())))
+}
+
+fn decimal_digits(code: Input<'_>) -> IResult<'_, Input<'_>> {
+    digits(|c| c.is_ascii_digit())(code)
+}
+
+fn digits<'a>(
+    is_digit: impl Fn(char) -> bool + Copy,
+) -> impl FnMut(Input<'a>) -> IResult<'a, Input<'a>> {
+    let separated_digit = preceded(char('_'), cut(context("digit", satisfy(is_digit))));
+    recognize(pair(
+        satisfy(is_digit),
+        many0(alt((satisfy(is_digit), separated_digit))),
+    ))
+}
+
+fn literal_text(code: Input<'_>) -> IResult<'_, Literal<'_>> {
+    alt((literal_raw_text, literal_escaped_text))(code)
+}
+
+fn literal_raw_text(code: Input<'_>) -> IResult<'_, Literal<'_>> {
+    let (code, hashes) = preceded(char('r'), take_while(|c| c == '#'))(code)?;
+    let (code, _) = char('"')(code)?;
+    let terminator = format!("\"{hashes}");
+    let (code, text) = cut(context("closing quote", take_until(terminator.as_str())))(code)?;
+    let (code, _) = tag(terminator.as_str())(code)?;
+    Ok((code, Literal::Text(Cow::Borrowed(text.into_fragment()))))
+}
+
+fn literal_escaped_text(code: Input<'_>) -> IResult<'_, Literal<'_>> {
+    let (mut code, _) = char('"')(code)?;
+    let mut text = Cow::Borrowed("");
+    loop {
+        let (rest, chunk) = take_till(|c| c == '"' || c == '\\')(code)?;
+        if text.is_empty() {
+            text = Cow::Borrowed(chunk.into_fragment());
+        } else {
+            text.to_mut().push_str(chunk.fragment());
+        }
+        let (rest, terminator) = cut(context("closing quote", alt((char('"'), char('\\')))))(rest)?;
+        if terminator == '"' {
+            return Ok((rest, Literal::Text(text)));
+        }
+        let (rest, escaped) = cut(context("escape sequence", escape_sequence))(rest)?;
+        text.to_mut().push(escaped);
+        code = rest;
+    }
+}
+
+fn escape_sequence(code: Input<'_>) -> IResult<'_, char> {
+    alt((
+        value('\n', char('n')),
+        value('\t', char('t')),
+        value('\r', char('r')),
+        value('\0', char('0')),
+        value('\\', char('\\')),
+        value('"', char('"')),
+        unicode_escape,
+    ))(code)
+}
+
+fn unicode_escape(code: Input<'_>) -> IResult<'_, char> {
+    let hex_digits = take_while_m_n(1, 6, |c: char| c.is_ascii_hexdigit());
+    map_opt(delimited(tag("u{"), hex_digits, char('}')), |hex: Input| {
+        char::from_u32(u32::from_str_radix(hex.fragment(), 16).unwrap())
+    })(code)
+}
+
+fn variable(code: Input<'_>) -> IResult<'_, ExpressionKind<'_>> {
+    let (code, identifier) = identifier(code)?;
+    Ok((code, ExpressionKind::Variable(identifier)))
 }
 
-fn call(code: &str) -> IResult<&str, Expression> {
+fn call(code: Input<'_>) -> IResult<'_, ExpressionKind<'_>> {
     let (code, function) = identifier(code)?;
     let (code, arguments) = call_arguments(code)?;
-    Ok((code, Expression::Call(function, arguments)))
+    Ok((code, ExpressionKind::Call(function, arguments)))
 }
 
-fn call_arguments(code: &str) -> IResult<&str, Vec<Expression>> {
-    delimited(char('('), separated_list0(tag(", "), expression), char(')'))(code)
+fn call_arguments(code: Input<'_>) -> IResult<'_, Vec<Expression<'_>>> {
+    list('(', bracketed_expression, ')')(code)
 }
@@ -0,0 +1,99 @@
+use crate::parser::token::{is_identifier_char, RESERVED_KEYWORD};
+use crate::parser::{IResult, Input};
+use nom::error::{VerboseError, VerboseErrorKind};
+use std::fmt::{Display, Formatter};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub snippet: String,
+    pub message: String,
+}
+
+impl ParseError {
+    pub(crate) fn new(source: &str, position: Input, message: String) -> ParseError {
+        let offset = position.location_offset();
+        let line_start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[offset..]
+            .find('\n')
+            .map_or(source.len(), |i| offset + i);
+        ParseError {
+            line: position.location_line() as usize,
+            column: position.get_utf8_column(),
+            snippet: source[line_start..line_end]
+                .trim_end_matches('\r')
+                .to_owned(),
+            message,
+        }
+    }
+
+    pub(crate) fn from_nom(source: &str, error: VerboseError<Input>) -> ParseError {
+        let (rest, kind) = &error.errors[0];
+        match kind {
+            VerboseErrorKind::Context(RESERVED_KEYWORD) => {
+                let end = rest.find(|c| !is_identifier_char(c)).unwrap_or(rest.len());
+                let keyword = &rest[..end];
+                let message = format!("expected identifier, found reserved keyword {keyword:?}");
+                return ParseError::new(source, rest.clone(), message);
+            }
+            VerboseErrorKind::Context(message) => {
+                return ParseError::new(source, rest.clone(), (*message).to_owned());
+            }
+            _ => {}
+        }
+        let contexts: Vec<_> = error
+            .errors
+            .iter()
+            .filter_map(|(input, kind)| match kind {
+                VerboseErrorKind::Context(context) => Some((input.location_offset(), *context)),
+                _ => None,
+            })
+            .collect();
+        let expected = contexts
+            .iter()
+            .rev()
+            .find(|(position, _)| *position == rest.location_offset())
+            .map(|(_, context)| (*context).to_owned())
+            .or(match kind {
+                VerboseErrorKind::Char(c) => Some(format!("{c:?}")),
+                _ => None,
+            });
+        let construct = contexts
+            .iter()
+            .find(|(position, context)| {
+                *position < rest.location_offset() && Some(*context) != expected.as_deref()
+            })
+            .map(|(_, context)| context);
+        let message = match (expected, construct) {
+            (Some(expected), Some(construct)) => format!("expected {expected} in {construct}"),
+            (Some(expected), None) => format!("expected {expected}"),
+            (None, Some(construct)) => format!("invalid {construct}"),
+            (None, None) => "invalid syntax".to_owned(),
+        };
+        ParseError::new(source, rest.clone(), message)
+    }
+}
+
+pub(crate) fn error<'a, O>(code: Input<'a>, message: &'static str) -> IResult<'a, O> {
+    let errors = vec![(code, VerboseErrorKind::Context(message))];
+    Err(nom::Err::Error(VerboseError { errors }))
+}
+
+pub(crate) fn failure<'a, O>(code: Input<'a>, message: &'static str) -> IResult<'a, O> {
+    let errors = vec![(code, VerboseErrorKind::Context(message))];
+    Err(nom::Err::Failure(VerboseError { errors }))
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let caret = " ".repeat(self.column - 1);
+        write!(
+            f,
+            "error at line {}, column {}: {}\n{}\n{caret}^",
+            self.line, self.column, self.message, self.snippet
+        )
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -0,0 +1,117 @@
+use crate::ast::{Comment, Node};
+use crate::parser::error::{error, failure};
+use crate::parser::{span, IResult, Input};
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while};
+use nom::character::complete::{char, multispace1, none_of, satisfy};
+use nom::combinator::{cut, not, opt, recognize, value, verify};
+use nom::error::context;
+use nom::multi::{many0, separated_list0};
+use nom::sequence::{delimited, pair, preceded, terminated, tuple};
+
+pub(crate) const RESERVED_KEYWORD: &str = "reserved keyword";
+
+const KEYWORDS: &[&str] = &[
+    "and", "break", "continue", "elif", "else", "enum", "false", "for", "func", "if", "in",
+    "match", "none", "not", "or", "return", "struct", "true", "while",
+];
+
+pub(crate) fn identifier(code: Input<'_>) -> IResult<'_, &str> {
+    let (rest, identifier) = word(code.clone())?;
+    if KEYWORDS.contains(identifier.fragment()) {
+        return error(code, RESERVED_KEYWORD);
+    }
+    Ok((rest, identifier.into_fragment()))
+}
+
+pub(crate) fn declared_identifier(code: Input<'_>) -> IResult<'_, &str> {
+    if reserved_keyword(code.clone()).is_ok() {
+        return failure(code, RESERVED_KEYWORD);
+    }
+    identifier(code)
+}
+
+pub(crate) fn reserved_keyword(code: Input<'_>) -> IResult<'_, Input<'_>> {
+    verify(word, |word: &Input| KEYWORDS.contains(word.fragment()))(code)
+}
+
+fn word(code: Input<'_>) -> IResult<'_, Input<'_>> {
+    context(
+        "identifier",
+        recognize(pair(
+            satisfy(is_identifier_start),
+            take_while(is_identifier_char),
+        )),
+    )(code)
+}
+
+pub(crate) fn keyword<'a>(word: &'static str) -> impl FnMut(Input<'a>) -> IResult<'a, Input<'a>> {
+    terminated(tag(word), not(satisfy(is_identifier_char)))
+}
+
+pub(crate) fn list<'a, O>(
+    open: char,
+    element: impl FnMut(Input<'a>) -> IResult<'a, O>,
+    close: char,
+) -> impl FnMut(Input<'a>) -> IResult<'a, Vec<O>> {
+    let separator = delimited(bracket_space, char(','), bracket_space);
+    let trailing_separator = opt(pair(char(','), bracket_space));
+    delimited(
+        pair(char(open), bracket_space),
+        separated_list0(separator, element),
+        cut(tuple((bracket_space, trailing_separator, char(close)))),
+    )
+}
+
+pub(crate) fn space(code: Input<'_>) -> IResult<'_, ()> {
+    let (code, _) = indentation(code)?;
+    Ok((code, ()))
+}
+
+pub(crate) fn bracket_space(code: Input<'_>) -> IResult<'_, ()> {
+    let (code, _) = many0(alt((value((), multispace1), value((), comment))))(code)?;
+    Ok((code, ()))
+}
+
+pub(crate) fn trivia(code: Input<'_>) -> IResult<'_, ()> {
+    let (code, _) = pair(indentation, opt(comment))(code)?;
+    Ok((code, ()))
+}
+
+pub(crate) fn comment(code: Input<'_>) -> IResult<'_, &str> {
+    let start = code.clone();
+    let lone_carriage_return = terminated(char('\r'), not(char('\n')));
+    let body = recognize(many0(alt((none_of("\r\n"), lone_carriage_return))));
+    let (code, text) = preceded(char('#'), body)(code)?;
+    let span = span(&start, &code);
+    code.extra.borrow_mut().insert(span.start, span);
+    Ok((code, text.into_fragment()))
+}
+
+pub(crate) fn take_comments(code: Input<'_>) -> Vec<Comment<'_>> {
+    let comments = code.extra.take();
+    let code = code.into_fragment();
+    let comments = comments.into_values().map(|span| Node {
+        kind: &code[span.start + 1..span.end],
+        span,
+    });
+    comments.collect()
+}
+
+pub(crate) fn line_ending(code: Input<'_>) -> IResult<'_, ()> {
+    let (code, _) = pair(opt(char('\r')), char('\n'))(code)?;
+    Ok((code, ()))
+}
+
+pub(crate) fn indentation(code: Input<'_>) -> IResult<'_, &str> {
+    let (code, indentation) = take_while(|c| c == ' ' || c == '\t')(code)?;
+    Ok((code, indentation.into_fragment()))
+}
+
+fn is_identifier_start(c: char) -> bool {
+    unicode_ident::is_xid_start(c) || c == '_'
+}
+
+pub(crate) fn is_identifier_char(c: char) -> bool {
+    unicode_ident::is_xid_continue(c)
+}
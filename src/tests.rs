@@ -1,103 +1,238 @@
-use crate::interpreter::run;
+use crate::ast::{ExpressionKind, Literal, Node, PatternKind, Program, Span, StatementKind};
+use crate::interpreter::{run, RuntimeError};
 use crate::parser::parse;
 
 #[test]
 fn literal_bool() {
-    let ast = parse("a = true\nb = false");
-    let state = run(&ast);
+    let ast = parse("a = true\nb = false").unwrap();
+    let state = run(&ast).unwrap();
     assert_eq!(state["a"], true);
     assert_eq!(state["b"], false);
 }
 
 #[test]
 fn literal_number() {
-    let ast = parse("a = 42");
-    let state = run(&ast);
+    let ast = parse("a = 42").unwrap();
+    let state = run(&ast).unwrap();
     assert_eq!(state["a"], 42);
 }
 
+#[test]
+fn literal_number_formats() {
+    let ast = parse("a = 0.5\nb = 1e3\nc = 2.5E-1\nd = 1_000_000\ne = 0x1F\nf = 0b1010\ng = 0o17")
+        .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(format!("{:?}", state["a"]), "0.5");
+    assert_eq!(state["b"], 1000);
+    assert_eq!(format!("{:?}", state["c"]), "0.25");
+    assert_eq!(state["d"], 1000000);
+    assert_eq!(state["e"], 31);
+    assert_eq!(state["f"], 10);
+    assert_eq!(state["g"], 15);
+}
+
+#[test]
+fn literal_number_debug_round_trip() {
+    let ast =
+        parse("a = [0.1, -2.5, 1e-7, 1.5e300, 12345678901234567890, true, \"text\"]").unwrap();
+    let state = run(&ast).unwrap();
+    let debug = format!("{:?}", state["a"]);
+    let code = format!("a = {debug}");
+    let ast = parse(&code).unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(format!("{:?}", state["a"]), debug);
+}
+
 #[test]
 fn literal_text() {
-    let ast = parse("a = \"Hello, world!\"");
-    let state = run(&ast);
+    let ast = parse("a = \"Hello, world!\"").unwrap();
+    let state = run(&ast).unwrap();
     assert_eq!(state["a"], "Hello, world!");
 }
 
+#[test]
+fn literal_text_escapes() {
+    let ast = parse(r#"a = "say \"hi\"\n\tback\\slash \u{1F600}""#).unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], "say \"hi\"\n\tback\\slash \u{1F600}");
+}
+
+#[test]
+fn literal_text_raw() {
+    let ast = parse(
+        r###"a = r"C:\path"
+b = r#"quote " and \n"#"###,
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], r"C:\path");
+    assert_eq!(state["b"], r#"quote " and \n"#);
+}
+
+#[test]
+fn literal_text_debug_round_trip() {
+    let ast = parse(r#"a = "line\nbreak \"quoted\" \\ tab\t nul\0 bell\u{7}""#).unwrap();
+    let state = run(&ast).unwrap();
+    let debug = format!("{:?}", state["a"]);
+    let code = format!("a = {debug}");
+    let ast = parse(&code).unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(format!("{:?}", state["a"]), debug);
+}
+
+#[test]
+fn literal_none() {
+    let ast = parse("a = none\nb = [1, none]").unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], ());
+    assert_eq!(state["b"][1], ());
+    assert_eq!(format!("{:?}", state["b"]), "[1.0, none]");
+}
+
+#[test]
+fn equal_none() {
+    let ast =
+        parse("a = none == none\nb = none != none\nc = 1 == none\nd = none != \"x\"").unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], true);
+    assert_eq!(state["b"], false);
+    assert_eq!(state["c"], false);
+    assert_eq!(state["d"], true);
+}
+
 #[test]
 fn expression_variable() {
-    let ast = parse("a = 42\nb = a");
-    let state = run(&ast);
+    let ast = parse("a = 42\nb = a").unwrap();
+    let state = run(&ast).unwrap();
     assert_eq!(state["b"], 42);
 }
 
 #[test]
 fn text_length() {
-    let ast = parse("a = \"hello\".length");
-    let state = run(&ast);
+    let ast = parse("a = \"hello\".length").unwrap();
+    let state = run(&ast).unwrap();
     assert_eq!(state["a"], 5);
 }
 
+#[test]
+fn text_length_unicode() {
+    let ast = parse("a = \"żółw\".length\nb = \"\u{1F600}!\".length").unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], 4);
+    assert_eq!(state["b"], 2);
+}
+
 #[test]
 fn add_number() {
-    let ast = parse("a = 2 + 2");
-    let state = run(&ast);
+    let ast = parse("a = 2 + 2").unwrap();
+    let state = run(&ast).unwrap();
     assert_eq!(state["a"], 4);
 }
 
 #[test]
 fn add_text() {
-    let ast = parse("a = \"a\" + \"b\"");
-    let state = run(&ast);
+    let ast = parse("a = \"a\" + \"b\"").unwrap();
+    let state = run(&ast).unwrap();
     assert_eq!(state["a"], "ab");
 }
 
 #[test]
 fn sub_number() {
-    let ast = parse("a = 13 - 8");
-    let state = run(&ast);
+    let ast = parse("a = 13 - 8").unwrap();
+    let state = run(&ast).unwrap();
     assert_eq!(state["a"], 5);
 }
 
 #[test]
 fn mul_number() {
-    let ast = parse("a = 2 * 3");
-    let state = run(&ast);
+    let ast = parse("a = 2 * 3").unwrap();
+    let state = run(&ast).unwrap();
     assert_eq!(state["a"], 6);
 }
 
 #[test]
 fn div_number() {
-    let ast = parse("a = 6 / 2");
-    let state = run(&ast);
+    let ast = parse("a = 6 / 2").unwrap();
+    let state = run(&ast).unwrap();
     assert_eq!(state["a"], 3);
 }
 
+#[test]
+fn mod_number() {
+    let ast = parse("a = 7 % 3\nb = (0 - 1) % 3").unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], 1);
+    assert_eq!(state["b"], 2);
+}
+
 #[test]
 fn equal_number() {
-    let ast = parse("a = 2 == 2\nb = 2 == 3\nc = 2 != 2\nd = 2 != 3");
-    let state = run(&ast);
+    let ast = parse("a = 2 == 2\nb = 2 == 3\nc = 2 != 2\nd = 2 != 3").unwrap();
+    let state = run(&ast).unwrap();
     assert_eq!(state["a"], true);
     assert_eq!(state["b"], false);
     assert_eq!(state["c"], false);
     assert_eq!(state["d"], true);
 }
 
+#[test]
+fn equal_bool() {
+    let ast = parse(
+        "a = true == true\nb = true == false\nc = false != true\nd = [true] == [true]\ne = 1 == \"1\"\nf = true != 1",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], true);
+    assert_eq!(state["b"], false);
+    assert_eq!(state["c"], true);
+    assert_eq!(state["d"], true);
+    assert_eq!(state["e"], false);
+    assert_eq!(state["f"], true);
+}
+
 #[test]
 fn equal_text() {
     let ast =
-        parse("a = \"a\" == \"a\"\nb = \"a\" == \"b\"\nc = \"a\" != \"a\"\nd = \"a\" != \"b\"");
-    let state = run(&ast);
+        parse("a = \"a\" == \"a\"\nb = \"a\" == \"b\"\nc = \"a\" != \"a\"\nd = \"a\" != \"b\"")
+            .unwrap();
+    let state = run(&ast).unwrap();
     assert_eq!(state["a"], true);
     assert_eq!(state["b"], false);
     assert_eq!(state["c"], false);
     assert_eq!(state["d"], true);
 }
 
+#[test]
+fn ordering_number() {
+    let ast = parse("a = 1 < 2\nb = 2 <= 2\nc = 1 > 2\nd = 3 >= 2\ne = 1 + 1 < 3 and 4 % 3 >= 1")
+        .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], true);
+    assert_eq!(state["b"], true);
+    assert_eq!(state["c"], false);
+    assert_eq!(state["d"], true);
+    assert_eq!(state["e"], true);
+}
+
+#[test]
+fn ordering_text() {
+    let ast = parse(
+        "a = \"apple\" < \"banana\"\nb = \"b\" <= \"a\"\nc = \"ab\" > \"a\"\nd = \"a\" >= \"a\"",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], true);
+    assert_eq!(state["b"], false);
+    assert_eq!(state["c"], true);
+    assert_eq!(state["d"], true);
+}
+
 #[test]
 fn and() {
     let ast =
-        parse("a = false and false\nb = false and true\nc = true and false\nd = true and true");
-    let state = run(&ast);
+        parse("a = false and false\nb = false and true\nc = true and false\nd = true and true")
+            .unwrap();
+    let state = run(&ast).unwrap();
     assert_eq!(state["a"], false);
     assert_eq!(state["b"], false);
     assert_eq!(state["c"], false);
@@ -106,8 +241,74 @@ fn and() {
 
 #[test]
 fn or() {
-    let ast = parse("a = false or false\nb = false or true\nc = true or false\nd = true or true");
-    let state = run(&ast);
+    let ast = parse("a = false or false\nb = false or true\nc = true or false\nd = true or true")
+        .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], false);
+    assert_eq!(state["b"], true);
+    assert_eq!(state["c"], true);
+    assert_eq!(state["d"], true);
+}
+
+#[test]
+fn short_circuit() {
+    let ast = parse(
+        "func pass(result)\n    return result\na = false and missing()\nb = true or missing()\nc = true and pass(false)\nd = false or pass(true)\nitems = [1, 2]\ni = 2\nfound = i != items.length and items[i] == 2",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], false);
+    assert_eq!(state["b"], true);
+    assert_eq!(state["c"], false);
+    assert_eq!(state["d"], true);
+    assert_eq!(state["found"], false);
+}
+
+#[test]
+fn precedence() {
+    let ast = parse("a = 1 + 2 * 3\nb = (1 + 2) * 3\nc = 2 * 3 + 1 == 7 and 1 == 1\nd = true or false and false")
+        .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], 7);
+    assert_eq!(state["b"], 9);
+    assert_eq!(state["c"], true);
+    assert_eq!(state["d"], true);
+}
+
+#[test]
+fn left_associativity() {
+    let ast = parse("a = 10 - 3 - 2\nb = 12 / 2 / 3\nc = 1 == 1 and 2 == 2 and 3 == 4\nd = \"a\" + \"b\" + \"c\"")
+        .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], 5);
+    assert_eq!(state["b"], 2);
+    assert_eq!(state["c"], false);
+    assert_eq!(state["d"], "abc");
+}
+
+#[test]
+fn postfix_chain() {
+    let ast = parse("a = [[1, 2], [3]][0][1]\nb = [\"abc\"][0].length + 1").unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], 2);
+    assert_eq!(state["b"], 4);
+}
+
+#[test]
+fn negate() {
+    let ast = parse("a = -5\nb = -2 * 3 + 10\nc = 1 - -1\nd = -[1, 2].length").unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], -5);
+    assert_eq!(state["b"], 4);
+    assert_eq!(state["c"], 2);
+    assert_eq!(state["d"], -2);
+}
+
+#[test]
+fn not() {
+    let ast = parse("a = not true\nb = not 1 == 2\nc = true and not false\nd = not false or true")
+        .unwrap();
+    let state = run(&ast).unwrap();
     assert_eq!(state["a"], false);
     assert_eq!(state["b"], true);
     assert_eq!(state["c"], true);
@@ -116,52 +317,1044 @@ fn or() {
 
 #[test]
 fn array() {
-    let ast = parse("a = [2, \"test\"]");
-    let state = run(&ast);
+    let ast = parse("a = [2, \"test\"]").unwrap();
+    let state = run(&ast).unwrap();
     assert_eq!(state["a"][0], 2);
     assert_eq!(state["a"][1], "test");
 }
 
 #[test]
 fn array_length() {
-    let ast = parse("a = [2, \"test\"].length");
-    let state = run(&ast);
+    let ast = parse("a = [2, \"test\"].length").unwrap();
+    let state = run(&ast).unwrap();
     assert_eq!(state["a"], 2);
 }
 
 #[test]
 fn array_index() {
-    let ast = parse("a = [2, \"test\"]\nb = a[0]\nc = a[1]");
-    let state = run(&ast);
+    let ast = parse("a = [2, \"test\"]\nb = a[0]\nc = a[1]").unwrap();
+    let state = run(&ast).unwrap();
     assert_eq!(state["b"], 2);
     assert_eq!(state["c"], "test");
 }
 
+#[test]
+fn array_index_assign() {
+    let ast = parse("a = [1, 2, 3]\nb = a\na[0] = 5\ni = 1\na[i + 1] = a[0] * 2").unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], &[5, 2, 10][..]);
+    assert_eq!(state["b"], &[1, 2, 3][..]);
+}
+
+#[test]
+fn array_nested_assign() {
+    let ast = parse(
+        "grid = [[0, 0], [0, 0]]\ni = 0\nwhile i < 2\n    j = 0\n    while j < 2\n        grid[i][j] = i * 2 + j\n        j = j + 1\n    i = i + 1",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["grid"][0], &[0, 1][..]);
+    assert_eq!(state["grid"][1], &[2, 3][..]);
+}
+
+#[test]
+fn compound_assign() {
+    let ast = parse(
+        "a = 10\na += 5\na -= 3\na *= 2\na /= 4\na %= 4\ns = \"ab\"\ns += \"c\"\ngrid = [[1, 2], [3, 4]]\ni = 0\ngrid[i + 1][i] *= 10\ngrid[0][1]+=1",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], 2);
+    assert_eq!(state["s"], "abc");
+    assert_eq!(state["grid"][0], &[1, 3][..]);
+    assert_eq!(state["grid"][1], &[30, 4][..]);
+}
+
+#[test]
+fn compound_assign_single_evaluation() {
+    let ast = parse(
+        "struct Counter(n)\n    func next()\n        self.n += 1\n        return self.n - 1\ncounter = Counter(0)\na = [10, 20, 30]\na[counter.next()] += 1\na[counter.next()] *= 2\ncalls = counter.n",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], &[11, 40, 30][..]);
+    assert_eq!(state["calls"], 2);
+}
+
+#[test]
+fn compound_assign_local() {
+    let ast = parse("n = 1\nfunc f()\n    n += 1\n    return n\nm = f()").unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["n"], 1);
+    assert_eq!(state["m"], 2);
+}
+
+#[test]
+fn func_global_copy() {
+    let ast = parse(
+        "g = [1, 2]\nm = {\"k\": 1}\nstruct P(x)\np = P(0)\nfunc f()\n    g[0] = 99\n    m[\"k\"] += 1\n    p.x = 1\n    return [g[0], m[\"k\"], p.x]\nlocal = f()",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["local"], &[99, 2, 1][..]);
+    assert_eq!(state["g"], &[1, 2][..]);
+    assert_eq!(state["m"]["k"], 1);
+    assert_eq!(format!("{:?}", state["p"]), "P { x: 0.0 }");
+}
+
+#[test]
+fn map() {
+    let ast = parse(
+        "m = {\"b\": 2, \"a\": 1, 3: \"three\",}\nempty = {}\nb = m[\"b\"]\nthree = m[1 + 2]\nlength = m.length\nkeys = m.keys()\nvalues = m.values()\nhas_a = m.contains(\"a\")\nhas_c = m.contains(\"c\")",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(
+        format!("{:?}", state["m"]),
+        "{\"b\": 2.0, \"a\": 1.0, 3.0: \"three\"}"
+    );
+    assert_eq!(format!("{:?}", state["empty"]), "{}");
+    assert_eq!(state["m"]["a"], 1);
+    assert_eq!(state["b"], 2);
+    assert_eq!(state["three"], "three");
+    assert_eq!(state["length"], 3);
+    assert_eq!(format!("{:?}", state["keys"]), "[\"b\", \"a\", 3.0]");
+    assert_eq!(format!("{:?}", state["values"]), "[2.0, 1.0, \"three\"]");
+    assert_eq!(state["has_a"], true);
+    assert_eq!(state["has_c"], false);
+}
+
+#[test]
+fn map_assign() {
+    let ast = parse(
+        "counts = {}\nfor word in [\"a\", \"b\", \"a\"]\n    if counts.contains(word)\n        counts[word] += 1\n    else\n        counts[word] = 1\nnested = {\"xs\": [1, 2]}\nnested[\"xs\"][0] = 5\nnested[\"m\"] = {}\nnested[\"m\"][\"k\"] = true",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["counts"]["a"], 2);
+    assert_eq!(state["counts"]["b"], 1);
+    assert_eq!(state["nested"]["xs"], &[5, 2][..]);
+    assert_eq!(state["nested"]["m"]["k"], true);
+}
+
+#[test]
+fn equal_map() {
+    let ast = parse(
+        "a = {\"x\": 1, \"y\": [1, 2]} == {\"y\": [1, 2], \"x\": 1}\nb = {\"x\": 1} != {\"x\": 2}\nc = {1: 1} == {\"1\": 1}\nd = [1, [2]] == [1, [2]]",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], true);
+    assert_eq!(state["b"], true);
+    assert_eq!(state["c"], false);
+    assert_eq!(state["d"], true);
+}
+
+#[test]
+fn struct_fields() {
+    let ast = parse(
+        "func origin()\n    return Point(0, 0)\nstruct Point(x, y)\np = Point(1, [2])\nq = p\np.x = 5\np.y[0] += 1\nx = p.x\no = origin()\nsame = o == Point(0, 0)",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(format!("{:?}", state["p"]), "Point { x: 5.0, y: [3.0] }");
+    assert_eq!(format!("{:?}", state["q"]), "Point { x: 1.0, y: [2.0] }");
+    assert_eq!(state["x"], 5);
+    assert_eq!(state["same"], true);
+}
+
+#[test]
+fn struct_methods() {
+    let ast = parse(
+        "struct Order(items, discount)\n    func total()\n        sum = 0\n        for item in self.items\n            sum += item\n        return sum - self.discount\n\n    func add(item)\n        self.items[self.items.length - 1] = item\n\n    func starts_with(prefix)\n        return true\norder = Order([1, 2, 0], 1)\nbefore = order.total()\norder.add(7)\nafter = order.total()\norders = [Order([5], 0)]\norders[0].add(6)\ncopy = Order([1], 0)\ncopy.total()\nlength = order.items.length\nown = order.starts_with(\"x\")\nbuiltin = \"abc\".starts_with(\"a\")",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["before"], 2);
+    assert_eq!(state["after"], 9);
+    assert_eq!(
+        format!("{:?}", state["orders"]),
+        "[Order { items: [6.0], discount: 0.0 }]"
+    );
+    assert_eq!(state["length"], 3);
+    assert_eq!(state["own"], true);
+    assert_eq!(state["builtin"], true);
+}
+
+#[test]
+fn enum_variants() {
+    let ast = parse(
+        "enum Shape\n    Circle(radius)\n    Rect(width, height)\n    Empty\na = Circle(2)\nb = Rect(1, 3)\nc = Empty\nsame = Circle(2) == a\nradius = a.radius",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(format!("{:?}", state["a"]), "Circle { radius: 2.0 }");
+    assert_eq!(
+        format!("{:?}", state["b"]),
+        "Rect { width: 1.0, height: 3.0 }"
+    );
+    assert_eq!(format!("{:?}", state["c"]), "Empty");
+    assert_eq!(state["same"], true);
+    assert_eq!(state["radius"], 2);
+}
+
+#[test]
+fn match_patterns() {
+    let ast = parse(
+        "enum Shape\n    Circle(radius)\n    Rect(width, height)\n    Empty\nfunc area(shape)\n    match shape\n        Circle(r)\n            return 3 * r * r\n        Rect(w, h) if w == h\n            return -1\n        Rect(w, h)\n            return w * h\n        Empty\n            return 0\nfunc describe(value)\n    match value\n        0\n            return \"zero\"\n        -1\n            return \"minus one\"\n        \"hi\" # greeting\n            return \"text\"\n        none\n            return \"nothing\"\n        [x, _, [y]]\n            return x + y\n        []\n            return \"empty\"\n        n if n > 100\n            return \"big\"\n        _\n            return \"other\"\na = [area(Circle(2)), area(Rect(2, 2)), area(Rect(2, 3)), area(Empty)]\nb = [describe(0), describe(-1), describe(\"hi\"), describe(none), describe([1, 2, [3]])]\nc = [describe([]), describe(101), describe(5)]",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], &[12, -1, 6, 0][..]);
+    assert_eq!(
+        format!("{:?}", state["b"]),
+        "[\"zero\", \"minus one\", \"text\", \"nothing\", 4.0]"
+    );
+    assert_eq!(
+        format!("{:?}", state["c"]),
+        "[\"empty\", \"big\", \"other\"]"
+    );
+}
+
+#[test]
+fn match_guard_bindings() {
+    let ast = parse(
+        "n = 10\nmatch 3\n    n if n > 5\n        a = 1\n    [m] if m\n        a = 2\n    _\n        a = 3\nfunc f(x)\n    match x\n        [x, y] if x > y\n            return y\n        _\n            return x\nb = f([1, 2])\nc = f([2, 1])",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["n"], 10);
+    assert_eq!(state["a"], 3);
+    assert!(!state.contains_key("m"));
+    assert_eq!(state["b"], &[1, 2][..]);
+    assert_eq!(state["c"], 1);
+}
+
+#[test]
+fn match_in_loop() {
+    let ast = parse(
+        "total = 0\nfor x in [1, 2, 3, 4]\n    match x\n        2\n            continue\n        4\n            break\n        _\n            total += x\nbound = none\nmatch [5, 6]\n    [first, second]\n        bound = first * second",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["total"], 4);
+    assert_eq!(state["bound"], 30);
+}
+
+#[test]
+fn struct_method_receiver() {
+    let ast = parse(
+        "struct Q(v)\n    func bump()\n        q.v = 100\n        self.v += 1\n    func set(v)\n        self.v = v\n        return 0\n    func add(n)\n        self.v += n\nq = Q(1)\nq.bump()\nbumped = q.v\nq.add(q.set(5))",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["bumped"], 2);
+    assert_eq!(format!("{:?}", state["q"]), "Q { v: 5.0 }");
+}
+
 #[test]
 fn func_return() {
-    let ast = parse("func f()\n    return 2\na = f()");
-    let state = run(&ast);
+    let ast = parse("func f()\n    return 2\na = f()").unwrap();
+    let state = run(&ast).unwrap();
     assert_eq!(state["a"], 2);
 }
 
+#[test]
+fn func_recursion() {
+    let ast = parse(
+        "func factorial(n)\n    if n == 0\n        return 1\n    return n * factorial(n - 1)\na = factorial(5)",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], 120);
+}
+
+#[test]
+fn func_mutual_recursion() {
+    let ast = parse(
+        "a = is_even(10)\nb = is_odd(7)\nfunc is_even(n)\n    if n == 0\n        return true\n    return is_odd(n - 1)\nfunc is_odd(n)\n    if n == 0\n        return false\n    return is_even(n - 1)",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], true);
+    assert_eq!(state["b"], true);
+}
+
+#[test]
+fn func_globals() {
+    let ast =
+        parse("limit = 3\nfunc f(x)\n    limit = x + limit\n    return limit\na = f(2)\nb = limit")
+            .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], 5);
+    assert_eq!(state["b"], 3);
+    assert!(!state.contains_key("x"));
+}
+
+#[test]
+fn identifiers() {
+    let ast = parse(
+        "count2 = 1\nuserId = 2\nMAX_SIZE = 3\n_tmp = 4\nπ = 5\nnaïve = π + count2\ntrueish = true",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["count2"], 1);
+    assert_eq!(state["userId"], 2);
+    assert_eq!(state["MAX_SIZE"], 3);
+    assert_eq!(state["_tmp"], 4);
+    assert_eq!(state["naïve"], 6);
+    assert_eq!(state["trueish"], true);
+}
+
+#[test]
+fn func_without_return() {
+    let ast = parse("func f(n)\n    n = n + 1\nf(1)\nb = f(2)").unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["b"], ());
+}
+
+#[test]
+fn func_bare_return() {
+    let ast =
+        parse("func f(n)\n    if n > 0\n        return\n    return n\na = f(1)\nb = f(0)").unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], ());
+    assert_eq!(state["b"], 0);
+}
+
+#[test]
+fn expression_statement() {
+    let ast = parse(
+        "func check(a)\n    a.starts_with(\"x\")\n    return a\nif true\n    check(\"y\")\n\"xyz\".starts_with(\"x\")\n1 + 2\nb = 3",
+    )
+    .unwrap();
+    assert!(matches!(ast.statements[1].kind, StatementKind::If(..)));
+    assert!(matches!(
+        ast.statements[2].kind,
+        StatementKind::Expression(_)
+    ));
+    let state = run(&ast).unwrap();
+    assert_eq!(state["b"], 3);
+}
+
+#[test]
+fn expression_statement_keyword() {
+    let ast = parse("func f()\n    return true\nnot f()\ntrue and f()\nnone\na = 1").unwrap();
+    for statement in &ast.statements[1..4] {
+        assert!(matches!(statement.kind, StatementKind::Expression(_)));
+    }
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], 1);
+}
+
 #[test]
 fn while_simple() {
-    let ast = parse("i = 0\nwhile i != 5\n    i = i + 1");
-    let state = run(&ast);
+    let ast = parse("i = 0\nwhile i != 5\n    i = i + 1").unwrap();
+    let state = run(&ast).unwrap();
     assert_eq!(state["i"], 5);
 }
 
+#[test]
+fn for_array() {
+    let ast = parse("total = 0\nfor x in [1, 2, 3]\n    total += x\nlast = x").unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["total"], 6);
+    assert_eq!(state["last"], 3);
+}
+
+#[test]
+fn for_text() {
+    let ast = parse("reversed = \"\"\nfor c in \"héllo\"\n    reversed = c + reversed").unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["reversed"], "olléh");
+}
+
+#[test]
+fn for_range() {
+    let ast = parse(
+        "n = 4\na = [0, 0, 0, 0]\nfor i in 0..n\n    a[i] = i * 10\nb = 0\nfor i in 1..=n\n    b += i\nc = [0, 0, 0, 0]\nk = 0\nfor i in 10 .. 0 step -3\n    c[k] = i\n    k += 1\nd = 0\nfor i in 0..=1 step 0.25\n    d += i * 4",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], &[0, 10, 20, 30][..]);
+    assert_eq!(state["b"], 10);
+    assert_eq!(state["c"], &[10, 7, 4, 1][..]);
+    assert_eq!(state["d"], 10);
+}
+
+#[test]
+fn for_range_return() {
+    let ast = parse(
+        "func first_square_above(n)\n    for i in 0..1000000000000\n        if i * i > n\n            return i\na = first_square_above(50)",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], 8);
+}
+
+#[test]
+fn break_continue() {
+    let ast = parse(
+        "i = 0\nodd = 0\nwhile true\n    i += 1\n    if i > 9\n        break\n    if i % 2 == 0\n        continue\n    odd += i\npairs = 0\nfor a in 0..5\n    for b in 0..5\n        if b > a\n            break\n        pairs += 1\n    if a == 3\n        break",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["i"], 10);
+    assert_eq!(state["odd"], 25);
+    assert_eq!(state["pairs"], 10);
+    assert_eq!(state["a"], 3);
+}
+
 #[test]
 fn if_simple() {
-    let ast = parse("i = 0\nif i == 0\n    i = 1\nif i == 0\n    i = 2");
-    let state = run(&ast);
+    let ast = parse("i = 0\nif i == 0\n    i = 1\nif i == 0\n    i = 2").unwrap();
+    let state = run(&ast).unwrap();
     assert_eq!(state["i"], 1);
 }
 
+#[test]
+fn if_else() {
+    let ast = parse("i = 1\nif i == 0\n    a = 1\nelse\n    a = 2").unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], 2);
+}
+
+#[test]
+fn if_elif() {
+    let ast = parse(
+        "func sign(x)\n    if x == 0\n        return \"zero\"\n    elif x == 1\n        return \"one\"\n    elif x == 2\n        return \"two\"\n    else\n        return \"many\"\n    return \"unreachable\"\na = sign(0)\nb = sign(2)\nc = sign(7)",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], "zero");
+    assert_eq!(state["b"], "two");
+    assert_eq!(state["c"], "many");
+}
+
+#[test]
+fn nested_blocks() {
+    let ast = parse(
+        "func count(n)\n  i = 0\n  c = 0\n  while i != n\n    i = i + 1\n    if i != 3\n      c = c + 1\n  return c\na = count(5)",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], 4);
+}
+
+#[test]
+fn nested_blocks_tabs() {
+    let ast = parse("i = 0\nwhile i != 2\n\ti = i + 1\n\tif i == 2\n\t\tj = i").unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["j"], 2);
+}
+
 #[test]
 fn text_starts_with() {
-    let ast = parse("a = \"hello\"\nb = a.starts_with(\"he\")\nc = a.starts_with(\"ha\")");
-    let state = run(&ast);
+    let ast = parse("a = \"hello\"\nb = a.starts_with(\"he\")\nc = a.starts_with(\"ha\")").unwrap();
+    let state = run(&ast).unwrap();
     assert_eq!(state["b"], true);
     assert_eq!(state["c"], false);
 }
+
+#[test]
+fn parse_error_position() {
+    let error = parse("a = 1\nb = )").unwrap_err();
+    assert_eq!(error.line, 2);
+    assert_eq!(error.column, 5);
+    assert_eq!(error.snippet, "b = )");
+    assert_eq!(error.message, "expected expression in assignment");
+}
+
+#[test]
+fn parse_error_trailing_input() {
+    let error = parse("a = 1 )").unwrap_err();
+    assert_eq!(error.line, 1);
+    assert_eq!(error.column, 7);
+    assert_eq!(error.message, "expected end of line");
+    let error = parse("a = 1 +").unwrap_err();
+    assert_eq!(error.column, 8);
+    assert_eq!(error.message, "expected expression in assignment");
+}
+
+#[test]
+fn parse_error_block() {
+    let error = parse("func f(a b)\n    return a").unwrap_err();
+    assert_eq!(error.line, 1);
+    assert_eq!(error.column, 10);
+    assert_eq!(error.message, "expected ')' in function declaration");
+}
+
+#[test]
+fn parse_error_duplicate_name() {
+    let error = parse("func f(a, b, a)\n    return a").unwrap_err();
+    assert_eq!(error.column, 14);
+    assert_eq!(error.message, "duplicate parameter");
+    let error = parse("struct P(x, x)").unwrap_err();
+    assert_eq!(error.column, 13);
+    assert_eq!(error.message, "duplicate field");
+    let error = parse("struct P(x)\n    func f(self)\n        return self").unwrap_err();
+    assert_eq!((error.line, error.column), (2, 12));
+    assert_eq!(error.message, "duplicate parameter");
+    let error =
+        parse("struct P(x)\n    func f()\n        return 1\n\n    func f()\n        return 2")
+            .unwrap_err();
+    assert_eq!((error.line, error.column), (5, 5));
+    assert_eq!(error.message, "duplicate method");
+    let error = parse("enum E\n    A(v, w, v)").unwrap_err();
+    assert_eq!((error.line, error.column), (2, 13));
+    assert_eq!(error.message, "duplicate field");
+}
+
+#[test]
+fn parse_error_reserved_keyword() {
+    let error = parse("a = 1\ntrue = 2").unwrap_err();
+    assert_eq!(error.line, 2);
+    assert_eq!(error.column, 1);
+    assert_eq!(
+        error.message,
+        "expected identifier, found reserved keyword \"true\""
+    );
+    let error = parse("func f(a, while)\n    return a").unwrap_err();
+    assert_eq!(error.column, 11);
+    assert_eq!(
+        error.message,
+        "expected identifier, found reserved keyword \"while\""
+    );
+    let error = parse("a = 2a").unwrap_err();
+    assert_eq!(error.column, 6);
+    let error = parse("none += 1").unwrap_err();
+    assert_eq!(
+        error.message,
+        "expected identifier, found reserved keyword \"none\""
+    );
+    let error = parse("for in in [1]\n    a = 1").unwrap_err();
+    assert_eq!(error.column, 5);
+    assert_eq!(
+        error.message,
+        "expected identifier, found reserved keyword \"in\""
+    );
+}
+
+#[test]
+fn parse_error_loop_control() {
+    let error = parse("a = 1\nbreak").unwrap_err();
+    assert_eq!(error.line, 2);
+    assert_eq!(error.column, 1);
+    assert_eq!(error.message, "'break' outside loop");
+    let error = parse("if true\n    continue").unwrap_err();
+    assert_eq!(error.message, "'continue' outside loop");
+    let error = parse("while true\n    func f()\n        break").unwrap_err();
+    assert_eq!((error.line, error.column), (3, 9));
+    assert_eq!(error.message, "'break' outside loop");
+}
+
+#[test]
+fn parse_error_map() {
+    let error = parse("m = {\"a\" 1}").unwrap_err();
+    assert_eq!(error.column, 10);
+    assert_eq!(error.message, "expected ':' in map");
+}
+
+#[test]
+fn parse_error_struct_method() {
+    let error = parse("struct Point(x, y)\n    x = 1").unwrap_err();
+    assert_eq!((error.line, error.column), (2, 5));
+    assert_eq!(
+        error.message,
+        "expected method declaration in struct declaration"
+    );
+}
+
+#[test]
+fn parse_error_text() {
+    let error = parse("a = \"abc").unwrap_err();
+    assert_eq!(error.column, 9);
+    assert_eq!(error.message, "expected closing quote in text");
+    let error = parse("a = \"a\\qb\"").unwrap_err();
+    assert_eq!(error.column, 8);
+    assert_eq!(error.message, "expected escape sequence in text");
+}
+
+#[test]
+fn parse_error_number() {
+    let error = parse("a = 0x_").unwrap_err();
+    assert_eq!(error.column, 7);
+    assert_eq!(error.message, "expected digit in number");
+    let error = parse("a = 0b__1").unwrap_err();
+    assert_eq!(error.column, 7);
+    let error = parse("a = 0o").unwrap_err();
+    assert_eq!(error.column, 7);
+    assert!(parse("a = 0x_1F").is_err());
+    assert!(parse("a = 0x1_F").is_ok());
+    for code in ["a = 1_", "a = 1__2", "a = 0x1_", "a = 1.5_e3", "a = 1e3_"] {
+        let error = parse(code).unwrap_err();
+        assert_eq!(error.message, "expected digit in number", "{code}");
+    }
+    assert!(parse("a = 1_2.3_4e5_6").is_ok());
+}
+
+#[test]
+fn parse_error_indentation() {
+    let error = parse("while true\n    a = 1\n      b = 2").unwrap_err();
+    assert_eq!(error.line, 3);
+    assert_eq!(error.column, 1);
+    assert_eq!(error.message, "expected matching indentation in while loop");
+}
+
+#[test]
+fn parse_error_missing_block() {
+    let error = parse(
+        "while false
+a = 1",
+    )
+    .unwrap_err();
+    assert_eq!(error.line, 1);
+    assert_eq!(error.column, 12);
+    assert_eq!(error.message, "expected indented block");
+    let error = parse(
+        "if x
+    a = 1
+else",
+    )
+    .unwrap_err();
+    assert_eq!(error.line, 3);
+    assert_eq!(error.column, 5);
+    assert_eq!(error.message, "expected indented block");
+    let error = parse(
+        "func f()
+    if x
+    a = 1",
+    )
+    .unwrap_err();
+    assert_eq!(error.line, 2);
+    assert_eq!(error.message, "expected indented block");
+    assert!(parse(
+        "struct Point(x, y)
+p = Point(1, 2)"
+    )
+    .is_ok());
+}
+
+#[test]
+fn comments_and_blank_lines() {
+    let ast = parse(
+        "\n# header\n\ni = 0   \nwhile i < 3  # loop\n\n    # step\n        # deeper comment\n    i = i + 1\n\n# between\nif i == 0\n    a = 1\n  # stray\nelse\n    a = \"# not a comment\"\n\n",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["i"], 3);
+    assert_eq!(state["a"], "# not a comment");
+    let comments: Vec<_> = ast.comments.iter().map(|comment| comment.kind).collect();
+    assert_eq!(
+        comments,
+        [
+            " header",
+            " loop",
+            " step",
+            " deeper comment",
+            " between",
+            " stray"
+        ]
+    );
+    assert_eq!(ast.comments[1].span.line, 5);
+    assert_eq!(ast.comments[1].span.column, 14);
+}
+
+#[test]
+fn crlf_line_endings() {
+    let ast = parse(
+        "a = 1\r\n\r\n# note\r\nif a == 1  # check\r\n    b = [\r\n        2,\r\n    ]\r\nelse\r\n    b = 3\r\n",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["b"], &[2][..]);
+    let comments: Vec<_> = ast.comments.iter().map(|comment| comment.kind).collect();
+    assert_eq!(comments, [" note", " check"]);
+    let error = parse("a = 1\r\nb = )\r\n").unwrap_err();
+    assert_eq!((error.line, error.column), (2, 5));
+    assert_eq!(error.snippet, "b = )");
+}
+
+#[test]
+fn comments_only() {
+    let ast = parse("# nothing here\n").unwrap();
+    assert!(ast.statements.is_empty());
+    assert_eq!(ast.comments.len(), 1);
+}
+
+#[test]
+fn comments_in_brackets() {
+    assert!(parse("a = [1, # lost\n").is_err());
+    let ast = parse(
+        "m = {  # open\n    r\"#raw\": [1],  # entry\n}\nx = m[\n    \"#raw\"  # key\n][0] # end",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["x"], 1);
+    let comments: Vec<_> = ast.comments.iter().map(|comment| comment.kind).collect();
+    assert_eq!(comments, [" open", " entry", " key", " end"]);
+}
+
+#[test]
+fn flexible_whitespace() {
+    let ast = parse(
+        "func f(a,b)\n    return a  -  b\nx=f(5,3)\ny = ( x *2 )+[ 1 ][0]\nz = -x\nw = not  x==2",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["x"], 2);
+    assert_eq!(state["y"], 5);
+    assert_eq!(state["z"], -2);
+    assert_eq!(state["w"], false);
+}
+
+#[test]
+fn multiline_lists() {
+    let ast = parse(
+        "func add(\n    a,\n    b,\n)\n    return a + b\nitems = [\n    1,  # first\n    2,\n\n    3,\n]\nsum = add(\n    items[0],\n    items[2]\n)\nempty = [\n]",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["items"], &[1, 2, 3][..]);
+    assert_eq!(state["sum"], 4);
+    assert_eq!(state["empty"], &[][..]);
+    assert_eq!(ast.comments[0].kind, " first");
+}
+
+#[test]
+fn multiline_operators() {
+    let ast = parse(
+        "a = (1 +\n 2)\nb = [1 +\n 2]\nc = {\"k\": 2\n    *  # twice\n    a}[\"k\"]\nd = (not\n    b[0] == 3 or -\n    a < 0)\ne = b[a -\n 3]\n",
+    )
+    .unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], 3);
+    assert_eq!(state["b"], &[3][..]);
+    assert_eq!(state["c"], 6);
+    assert_eq!(state["d"], true);
+    assert_eq!(state["e"], 3);
+    assert_eq!(ast.comments[0].kind, " twice");
+    assert!(parse("a = 1 +\n 2").is_err());
+}
+
+#[test]
+fn parse_error_unclosed_list() {
+    let error = parse("a = [1,\n2").unwrap_err();
+    assert_eq!(error.line, 2);
+    assert_eq!(error.column, 2);
+    assert_eq!(error.message, "expected ']' in array");
+}
+
+#[test]
+fn parse_error_match() {
+    let error = parse("match x\n    Some(a b)\n        c = 1").unwrap_err();
+    assert_eq!(error.line, 2);
+    assert_eq!(error.column, 12);
+    assert_eq!(error.message, "expected ')' in pattern");
+    let error = parse("enum = 1").unwrap_err();
+    assert_eq!(error.column, 6);
+    assert_eq!(error.message, "expected identifier in enum declaration");
+}
+
+#[test]
+fn match_spans() {
+    let code = "match x\n    [a, B(c)] if a\n        d = 1";
+    let ast = parse(code).unwrap();
+    let StatementKind::Match(_, arms) = &ast.statements[0].kind else {
+        panic!("expected match, got {:?}", ast.statements[0]);
+    };
+    assert_eq!((arms[0].span.start, arms[0].span.end), (12, code.len()));
+    let pattern = &arms[0].kind.pattern;
+    assert_eq!((pattern.span.start, pattern.span.end), (12, 21));
+    let PatternKind::Array(elements) = &pattern.kind else {
+        panic!("expected array pattern, got {pattern:?}");
+    };
+    assert_eq!((elements[1].span.line, elements[1].span.column), (2, 9));
+    assert_eq!((elements[1].span.start, elements[1].span.end), (16, 20));
+}
+
+#[test]
+fn spans() {
+    let ast = parse("a = 1\nb = [a, \"é\"] + c").unwrap();
+    let statement = &ast.statements[1];
+    assert_eq!(
+        statement.span,
+        Span {
+            start: 6,
+            end: 23,
+            line: 2,
+            column: 1,
+        }
+    );
+    let StatementKind::Assign(target, expression) = &statement.kind else {
+        panic!("expected assignment, got {statement:?}");
+    };
+    let ExpressionKind::BinaryOperator(lhs, _, rhs) = &expression.kind else {
+        panic!("expected binary operator, got {expression:?}");
+    };
+    assert_eq!((lhs.span.start, lhs.span.end), (10, 19));
+    assert_eq!((rhs.span.start, rhs.span.column), (22, 16));
+    assert_eq!((target.span.start, target.span.end), (6, 7));
+}
+
+#[test]
+fn error_undefined_variable() {
+    let ast = parse("a = b").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(error, RuntimeError::UndefinedVariable(name) if name == "b"));
+}
+
+#[test]
+fn error_invalid_operands() {
+    let ast = parse("a = 1 + \"b\"").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(
+        error,
+        RuntimeError::InvalidOperands { lhs, rhs, .. } if lhs == 1 && rhs == "b"
+    ));
+}
+
+#[test]
+fn error_invalid_operand() {
+    let ast = parse("a = not 1").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(
+        error,
+        RuntimeError::InvalidOperand { operand, .. } if operand == 1
+    ));
+}
+
+#[test]
+fn error_index_out_of_bounds() {
+    let ast = parse("a = [1, 2][2]").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(
+        error,
+        RuntimeError::IndexOutOfBounds { index, length: 2 } if index == 2.
+    ));
+}
+
+#[test]
+fn error_expression_statement() {
+    let ast = parse("a = 1\nlog(a)").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(error, RuntimeError::UndefinedFunction(name) if name == "log"));
+}
+
+#[test]
+fn error_assign_target() {
+    let ast = parse("a = [1, 2]\na[2] = 0").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(
+        error,
+        RuntimeError::IndexOutOfBounds { index, length: 2 } if index == 2.
+    ));
+    let ast = parse("a = [[1], 2]\na[1][0] = 0").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(error, RuntimeError::InvalidIndex { array, .. } if array == 2));
+    let ast = parse("a = [1]\na[\"x\"] = 0").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(error, RuntimeError::InvalidIndex { index, .. } if index == "x"));
+    let ast = parse("a = \"text\"\na.length = 0").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(error, RuntimeError::UnknownMember { member, .. } if member == "length"));
+    let ast = parse("b[0] = 1").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(error, RuntimeError::UndefinedVariable(name) if name == "b"));
+}
+
+#[test]
+fn error_assign_target_ast() {
+    let span = Span {
+        start: 0,
+        end: 0,
+        line: 1,
+        column: 1,
+    };
+    let expression = |kind| Node { kind, span };
+    let target = expression(ExpressionKind::Call("f", Vec::new()));
+    let value = expression(ExpressionKind::Literal(Literal::Number(1.)));
+    let program = Program {
+        statements: vec![Node {
+            kind: StatementKind::Assign(target, value),
+            span,
+        }],
+        comments: Vec::new(),
+    };
+    let error = run(&program).unwrap_err();
+    assert!(matches!(error, RuntimeError::InvalidAssignTarget));
+}
+
+#[test]
+fn error_compound_assign() {
+    let ast = parse("a += 1").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(error, RuntimeError::UndefinedVariable(name) if name == "a"));
+    let ast = parse("a = [\"x\"]\na[0] -= 1").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(error, RuntimeError::InvalidOperands { lhs, .. } if lhs == "x"));
+}
+
+#[test]
+fn error_for() {
+    let ast = parse("for x in 5\n    a = x").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(error, RuntimeError::InvalidIterable(value) if value == 5));
+    let ast = parse("for x in 0..5 step 0\n    a = x").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(error, RuntimeError::InvalidRange(step) if step == 0));
+    let ast = parse("for x in 0..\"5\"\n    a = x").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(error, RuntimeError::InvalidRange(end) if end == "5"));
+}
+
+#[test]
+fn error_map() {
+    let ast = parse("m = {\"a\": 1}\nb = m[\"b\"]").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(error, RuntimeError::MissingKey(key) if key == "b"));
+    let ast = parse("m = {\"a\": 1}\nm[\"b\"] += 1").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(error, RuntimeError::MissingKey(key) if key == "b"));
+    let ast = parse("m = {[1]: 1}").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(error, RuntimeError::InvalidKey(key) if key == &[1][..]));
+    let ast = parse("m = {}\nm[true] = 1").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(error, RuntimeError::InvalidKey(key) if key == true));
+    let ast = parse("m = {}\nm[0 / 0] = 1").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert_eq!(error.to_string(), "can't use NaN as map key");
+    let ast = parse("m = {0 / 0: 1}").unwrap();
+    assert!(matches!(run(&ast), Err(RuntimeError::InvalidKey(_))));
+}
+
+#[test]
+fn error_struct() {
+    let ast = parse("struct Point(x, y)\np = Point(1, 2)\nz = p.z").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert_eq!(error.to_string(), "struct \"Point\" has no field \"z\"");
+    let ast = parse("struct Point(x, y)\np = Point(1, 2)\np.xx = 3").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(
+        error,
+        RuntimeError::UnknownField { structure, field } if structure == "Point" && field == "xx"
+    ));
+    let ast = parse("struct Point(x, y)\np = Point(1)").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(
+        error,
+        RuntimeError::FieldCount { structure, expected: 2, found: 1 } if structure == "Point"
+    ));
+}
+
+#[test]
+fn error_struct_method() {
+    let ast = parse("struct Point(x, y)\n    func norm()\n        return self.x\np = Point(1, 2)\nn = p.length()").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(error, RuntimeError::UnknownMethod { method, .. } if method == "length"));
+    let ast = parse("struct Point(x, y)\n    func norm()\n        return self.x\np = Point(1, 2)\nn = p.norm(1)").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(error, RuntimeError::ArgumentCount { function, .. } if function == "norm"));
+}
+
+#[test]
+fn error_match() {
+    let ast = parse("enum Option\n    Some(value)\n    Nothing\nmatch Some(1)\n    Nothing\n        a = 1\n    Some(x) if x > 1\n        a = 2").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert_eq!(error.to_string(), "no match arm for Some { value: 1.0 }");
+    let ast = parse("match 3\n    [x]\n        a = x").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(error, RuntimeError::NoMatch(value) if value == 3));
+}
+
+#[test]
+fn error_match_pattern() {
+    let ast = parse("enum E\n    A\n    B(v)\nmatch B(1)\n    A(x)\n        a = 1\n    B\n        a = 2\n    _\n        a = 3").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert_eq!(error.to_string(), "\"A\" takes 0 fields but 1 were given");
+    let ast = parse("enum E\n    A\n    B(v)\nmatch A\n    B\n        a = 1\n    _\n        a = 2")
+        .unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(
+        error,
+        RuntimeError::FieldCount { structure, expected: 1, found: 0 } if structure == "B"
+    ));
+    let ast =
+        parse("enum E\n    A\nmatch [1, 2]\n    [A, A]\n        a = 0\n    [a, a]\n        a = 1")
+            .unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(error, RuntimeError::DuplicateBinding(name) if name == "a"));
+}
+
+#[test]
+fn error_variant_method() {
+    let ast = parse("enum Color\n    Red\nname = Red.name()").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(
+        error,
+        RuntimeError::UnknownMethod { object, method, .. } if method == "name" && format!("{object:?}") == "Red"
+    ));
+}
+
+#[test]
+fn error_duplicate_declaration() {
+    let ast = parse("enum A\n    X(v)\nenum B\n    X").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(error, RuntimeError::DuplicateDeclaration(name) if name == "X"));
+    let ast = parse("enum A\n    X(v)\nstruct X(w)\nx = X(1)").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert_eq!(error.to_string(), "\"X\" is already declared");
+    let ast =
+        parse("func f()\n    return 1\nfor i in 0..2\n    func f()\n        return 2").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(error, RuntimeError::DuplicateDeclaration(name) if name == "f"));
+    for code in [
+        "enum E\n    A\nenum E\n    B",
+        "struct E(x)\nenum E\n    A",
+        "func E()\n    return 1\nenum E\n    A",
+        "enum E\n    E",
+    ] {
+        let error = run(&parse(code).unwrap()).unwrap_err();
+        assert!(matches!(error, RuntimeError::DuplicateDeclaration(name) if name == "E"));
+    }
+    let ast = parse("for i in 0..3\n    func g()\n        return i\na = g()").unwrap();
+    let state = run(&ast).unwrap();
+    assert_eq!(state["a"], 2);
+}
+
+#[test]
+fn error_argument_count() {
+    let ast = parse("func f(a)\n    return a\nb = f(1, 2)").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(
+        error,
+        RuntimeError::ArgumentCount {
+            expected: 1,
+            found: 2,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn error_call_depth_small_stack() {
+    let thread = std::thread::Builder::new().stack_size(2 << 20).spawn(|| {
+        let ast = parse(
+            "func f(n)\n    if n == 0\n        return 0\n    return f(n - 1) + 1\na = f(20)\nb = f(1000)",
+        )
+        .unwrap();
+        match run(&ast) {
+            Err(RuntimeError::CallDepthExceeded(name)) => name == "f",
+            _ => false,
+        }
+    });
+    assert!(thread.unwrap().join().unwrap());
+}
+
+#[test]
+fn error_call_depth_exceeded() {
+    let ast = parse("func f(n)\n    return f(n + 1)\na = f(0)").unwrap();
+    let error = run(&ast).unwrap_err();
+    assert!(matches!(error, RuntimeError::CallDepthExceeded(name) if name == "f"));
+}